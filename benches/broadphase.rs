@@ -0,0 +1,107 @@
+//! Headless scenario comparing the spatial hash broadphase against brute force
+//! pair testing for 2,000 colliders. Run with `cargo bench --bench broadphase`.
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[allow(dead_code)]
+#[path = "../src/broadphase.rs"]
+mod broadphase;
+
+use broadphase::{overlaps, SpatialHash};
+
+const COLLIDER_COUNT: usize = 2_000;
+const WALL_COUNT: usize = 40;
+const ARENA_SIZE: Vec2 = Vec2::new(1600., 1200.);
+const CELL_SIZE: f32 = 32.;
+const FRAMES: u32 = 100;
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut frames: Vec<Vec<Rect>> = Vec::new();
+
+    let walls: Vec<Rect> = (0..WALL_COUNT)
+        .map(|_| {
+            let size = if rng.gen_bool(0.5) {
+                Vec2::new(rng.gen_range(48.0..400.0), 16.)
+            } else {
+                Vec2::new(16., rng.gen_range(48.0..400.0))
+            };
+
+            Rect::from_center_size(random_point(&mut rng), size)
+        })
+        .collect();
+
+    let mut positions: Vec<Vec2> = (0..COLLIDER_COUNT - WALL_COUNT)
+        .map(|_| random_point(&mut rng))
+        .collect();
+
+    for _ in 0..FRAMES {
+        let mut rects = walls.clone();
+
+        for (i, position) in positions.iter_mut().enumerate() {
+            *position += Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+
+            let size = if i % 4 == 0 {
+                Vec2::new(8., 13.)
+            } else {
+                Vec2::splat(15.)
+            };
+
+            rects.push(Rect::from_center_size(*position, size));
+        }
+
+        frames.push(rects);
+    }
+
+    let mut hash = SpatialHash::new(CELL_SIZE);
+    let mut hash_pairs = 0;
+
+    let hash_time = measure(&frames, |rects| {
+        hash.clear();
+
+        for (i, rect) in rects.iter().enumerate() {
+            hash.insert(*rect, i);
+        }
+
+        hash.for_each_overlapping_pair(|_, _| hash_pairs += 1);
+    });
+
+    let mut brute_force_pairs = 0;
+
+    let brute_force_time = measure(&frames, |rects| {
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                if overlaps(*a, *b) {
+                    brute_force_pairs += 1;
+                }
+            }
+        }
+    });
+
+    assert_eq!(hash_pairs, brute_force_pairs, "broadphase missed pairs");
+
+    println!("{COLLIDER_COUNT} colliders, {FRAMES} frames");
+    println!("pairs per frame: {}", hash_pairs / FRAMES as usize);
+    println!("spatial hash:    {:?} per frame", hash_time / FRAMES);
+    println!("brute force:     {:?} per frame", brute_force_time / FRAMES);
+}
+
+fn random_point(rng: &mut impl Rng) -> Vec2 {
+    Vec2::new(
+        rng.gen_range(0.0..ARENA_SIZE.x),
+        rng.gen_range(0.0..ARENA_SIZE.y),
+    )
+}
+
+fn measure(frames: &[Vec<Rect>], mut run: impl FnMut(&[Rect])) -> Duration {
+    let start = Instant::now();
+
+    for rects in frames {
+        run(rects);
+    }
+
+    start.elapsed()
+}
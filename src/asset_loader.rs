@@ -1,9 +1,37 @@
-use bevy::prelude::*;
+use std::{fmt, marker::PhantomData};
 
+use bevy::{
+    asset::{io::Reader, AssetLoadFailedEvent, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+use serde::de::DeserializeOwned;
+
+const TILE_SIZE: u32 = 16;
+const TILEMAP_COLUMNS: u32 = 12;
+const TILEMAP_ROWS: u32 = 11;
+
+/// The packed tilemap every character and item sprite is cut from.
 #[derive(Resource, Debug, Default)]
 pub struct SpriteAssets {
-    pub ghost: Handle<Image>,
-    pub dagger: Handle<Image>,
+    pub tilemap: Handle<Image>,
+    pub tilemap_layout: Handle<TextureAtlasLayout>,
+}
+
+impl SpriteAssets {
+    /// Sprite showing tile `index` of the tilemap, counted row by row.
+    pub fn tile(&self, index: usize, transform: Transform) -> (SpriteBundle, TextureAtlas) {
+        (
+            SpriteBundle {
+                texture: self.tilemap.clone(),
+                transform,
+                ..default()
+            },
+            TextureAtlas {
+                layout: self.tilemap_layout.clone(),
+                index,
+            },
+        )
+    }
 }
 
 pub struct AssetLoaderPlugin;
@@ -15,9 +43,169 @@ impl Plugin for AssetLoaderPlugin {
     }
 }
 
-fn load_assets(mut sprites: ResMut<SpriteAssets>, asset_server: Res<AssetServer>) {
+fn load_assets(
+    mut sprites: ResMut<SpriteAssets>,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
     *sprites = SpriteAssets {
-        ghost: asset_server.load("tile_0121.png"),
-        dagger: asset_server.load("tile_0103.png"),
+        tilemap: asset_server.load("tilemap_packed.png"),
+        tilemap_layout: layouts.add(TextureAtlasLayout::from_grid(
+            UVec2::splat(TILE_SIZE),
+            TILEMAP_COLUMNS,
+            TILEMAP_ROWS,
+            None,
+            None,
+        )),
+    }
+}
+
+/// Gameplay data stored as a RON file.
+pub trait RonAsset: Asset + DeserializeOwned {
+    const EXTENSIONS: &'static [&'static str];
+
+    /// Called after deserializing and before validating, to normalize the data.
+    fn prepare(&mut self) {}
+
+    /// Rejects values that parse but make no sense, failing the load with the message.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub struct RonAssetLoader<T>(PhantomData<T>);
+
+impl<T> Default for RonAssetLoader<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[derive(Debug)]
+pub enum RonAssetError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Invalid(String),
+}
+
+impl fmt::Display for RonAssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RonAssetError::Io(error) => write!(f, "could not read asset: {error}"),
+            RonAssetError::Ron(error) => write!(f, "could not parse asset: {error}"),
+            RonAssetError::Invalid(error) => write!(f, "invalid asset: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RonAssetError {}
+
+impl<T: RonAsset> AssetLoader for RonAssetLoader<T> {
+    type Asset = T;
+    type Settings = ();
+    type Error = RonAssetError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<T, RonAssetError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(RonAssetError::Io)?;
+
+        let mut asset: T = ron::de::from_bytes(&bytes).map_err(RonAssetError::Ron)?;
+        asset.prepare();
+        asset.validate().map_err(RonAssetError::Invalid)?;
+
+        Ok(asset)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        T::EXTENSIONS
+    }
+}
+
+/// Loads the `RonAsset` at `path` and keeps a copy of it as a resource, replaced
+/// whenever the file changes on disk while the game runs with the `hot_reload` feature.
+pub struct RonResourcePlugin<T> {
+    path: &'static str,
+    marker: PhantomData<T>,
+}
+
+impl<T> RonResourcePlugin<T> {
+    pub fn new(path: &'static str) -> Self {
+        Self {
+            path,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: RonAsset + Resource + Clone> Plugin for RonResourcePlugin<T> {
+    fn build(&self, app: &mut App) {
+        let path = self.path;
+
+        app.init_asset::<T>()
+            .init_asset_loader::<RonAssetLoader<T>>()
+            .init_resource::<LoadErrors>()
+            .add_systems(
+                Startup,
+                move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                    commands.insert_resource(RonResourceHandle::<T>(asset_server.load(path)));
+                },
+            )
+            .add_systems(PreUpdate, (apply_ron_resource::<T>, report_ron_errors::<T>));
+    }
+}
+
+/// Files that failed to load or validate, by path. A file that loads again after
+/// being fixed is removed.
+#[derive(Resource, Debug, Default)]
+pub struct LoadErrors(pub Vec<(String, String)>);
+
+#[derive(Resource, Debug)]
+struct RonResourceHandle<T: Asset>(Handle<T>);
+
+fn apply_ron_resource<T: RonAsset + Resource + Clone>(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<T>>,
+    mut errors: ResMut<LoadErrors>,
+    handle: Res<RonResourceHandle<T>>,
+    assets: Res<Assets<T>>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+
+        if let Some(asset) = assets.get(&handle.0) {
+            let path = handle
+                .0
+                .path()
+                .map_or(String::new(), |path| path.to_string());
+
+            info!("Applied {path}");
+            errors.0.retain(|(failed, _)| *failed != path);
+            commands.insert_resource(asset.clone());
+        }
+    }
+}
+
+/// Records why the file failed so the loading screen can show it. A resource that
+/// was already applied keeps its previous values.
+fn report_ron_errors<T: RonAsset + Resource + Clone>(
+    mut events: EventReader<AssetLoadFailedEvent<T>>,
+    mut errors: ResMut<LoadErrors>,
+    handle: Res<RonResourceHandle<T>>,
+) {
+    for event in events.read().filter(|event| event.id == handle.0.id()) {
+        let path = event.path.to_string();
+
+        errors.0.retain(|(failed, _)| *failed != path);
+        errors.0.push((path, event.error.to_string()));
     }
 }
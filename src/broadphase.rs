@@ -0,0 +1,166 @@
+use bevy::{prelude::*, utils::HashMap};
+
+/// Uniform grid that buckets axis-aligned rectangles by the cells they cover,
+/// so only rectangles sharing a cell are ever tested against each other.
+#[derive(Debug)]
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<usize>>,
+    entries: Vec<Entry<T>>,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    rect: Rect,
+    cells: IRect,
+    value: T,
+}
+
+impl<T: Copy> SpatialHash<T> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Removes every entry while keeping the allocations of cells that were in use.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cells.retain(|_, bucket| {
+            let in_use = !bucket.is_empty();
+            bucket.clear();
+            in_use
+        });
+    }
+
+    pub fn insert(&mut self, rect: Rect, value: T) {
+        let index = self.entries.len();
+        let cells = self.cells_covering(rect);
+
+        for y in cells.min.y..=cells.max.y {
+            for x in cells.min.x..=cells.max.x {
+                self.cells.entry(IVec2::new(x, y)).or_default().push(index);
+            }
+        }
+
+        self.entries.push(Entry { rect, cells, value });
+    }
+
+    /// Calls `f` once for every pair of overlapping rectangles.
+    pub fn for_each_overlapping_pair(&self, mut f: impl FnMut(T, T)) {
+        for (cell, bucket) in self.cells.iter() {
+            for (i, &index_a) in bucket.iter().enumerate() {
+                let a = &self.entries[index_a];
+
+                for &index_b in &bucket[i + 1..] {
+                    let b = &self.entries[index_b];
+
+                    // A pair sharing several cells is only reported from the first
+                    // cell of its shared area.
+                    if a.cells.min.max(b.cells.min) != *cell {
+                        continue;
+                    }
+
+                    if overlaps(a.rect, b.rect) {
+                        f(a.value, b.value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls `f` once for every entry whose rectangle overlaps `rect`.
+    pub fn query_rect(&self, rect: Rect, mut f: impl FnMut(T)) {
+        let cells = self.cells_covering(rect);
+
+        for y in cells.min.y..=cells.max.y {
+            for x in cells.min.x..=cells.max.x {
+                let cell = IVec2::new(x, y);
+
+                let Some(bucket) = self.cells.get(&cell) else {
+                    continue;
+                };
+
+                for &index in bucket {
+                    let entry = &self.entries[index];
+
+                    // Entries covering several queried cells are only reported once.
+                    if entry.cells.min.max(cells.min) != cell {
+                        continue;
+                    }
+
+                    if overlaps(entry.rect, rect) {
+                        f(entry.value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn cells_covering(&self, rect: Rect) -> IRect {
+        IRect::from_corners(
+            (rect.min / self.cell_size).floor().as_ivec2(),
+            (rect.max / self.cell_size).floor().as_ivec2(),
+        )
+    }
+}
+
+pub fn overlaps(a: Rect, b: Rect) -> bool {
+    !a.intersect(b).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(hash: &SpatialHash<u32>) -> Vec<(u32, u32)> {
+        let mut pairs = vec![];
+        hash.for_each_overlapping_pair(|a, b| pairs.push((a.min(b), a.max(b))));
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn pairs_sharing_several_cells_are_reported_once() {
+        let mut hash = SpatialHash::new(10.);
+        hash.insert(Rect::new(0., 0., 35., 35.), 0);
+        hash.insert(Rect::new(5., 5., 40., 40.), 1);
+        hash.insert(Rect::new(-25., -25., -15., -15.), 2);
+
+        assert_eq!(pairs(&hash), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn rectangles_sharing_a_cell_without_overlapping_are_not_paired() {
+        let mut hash = SpatialHash::new(10.);
+        hash.insert(Rect::new(0., 0., 2., 2.), 0);
+        hash.insert(Rect::new(5., 5., 8., 8.), 1);
+
+        assert!(pairs(&hash).is_empty());
+    }
+
+    #[test]
+    fn cleared_hash_reports_nothing() {
+        let mut hash = SpatialHash::new(10.);
+        hash.insert(Rect::new(0., 0., 20., 20.), 0);
+        hash.insert(Rect::new(5., 5., 25., 25.), 1);
+        hash.clear();
+        hash.insert(Rect::new(100., 100., 110., 110.), 2);
+
+        assert!(pairs(&hash).is_empty());
+    }
+
+    #[test]
+    fn query_reports_entries_covering_several_cells_once() {
+        let mut hash = SpatialHash::new(10.);
+        hash.insert(Rect::new(0., 0., 45., 45.), 0);
+        hash.insert(Rect::new(60., 60., 70., 70.), 1);
+
+        let mut found = vec![];
+        hash.query_rect(Rect::new(-5., -5., 50., 50.), |value| found.push(value));
+
+        assert_eq!(found, vec![0]);
+    }
+}
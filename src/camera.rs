@@ -1,14 +1,16 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 
-use crate::{player::Player, schedule::InGame};
+use crate::{player::Player, schedule::Presentation};
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_camera)
-            .add_systems(Update, camera_follows_player.in_set(InGame::EntityUpdate));
+        app.add_systems(Startup, spawn_camera).add_systems(
+            PostUpdate,
+            camera_follows_player.in_set(Presentation::FollowCamera),
+        );
     }
 }
 
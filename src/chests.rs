@@ -0,0 +1,255 @@
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::{
+    asset_loader::SpriteAssets,
+    collision::{Collider, CollisionStarted, Layer},
+    config::GameplayConfig,
+    health::{DamageSystems, DeathKind, EntityDied},
+    player::Player,
+    schedule::InGame,
+    state::{GameState, RunEntity},
+    stats::{Stat, Stats},
+    upgrades::{Choice, PlayerUpgrades},
+};
+
+pub struct ChestsPlugin;
+
+impl Plugin for ChestsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (drop_chests.after(DamageSystems), collect_chests).in_set(InGame::ProcessCombat),
+        )
+        .add_systems(
+            Update,
+            show_chest
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<ChestReveal>)),
+        )
+        .add_systems(OnEnter(GameState::Loading), discard_chest_reveal)
+        .add_systems(OnEnter(GameState::OpenChest), spawn_chest_screen)
+        .add_systems(
+            Update,
+            reveal_rewards.run_if(in_state(GameState::OpenChest)),
+        );
+    }
+}
+
+const CHEST_SPRITE: usize = 89;
+const CHEST_RADIUS: f32 = 6.;
+/// Below enemies and projectiles, above the level.
+const CHEST_Z: f32 = 50.;
+/// Seconds between two rewards appearing on the chest screen.
+const REVEAL_INTERVAL: f32 = 0.6;
+
+#[derive(Component, Debug)]
+struct Chest;
+
+/// Upgrades granted by an opened chest, shown one by one on the chest screen.
+#[derive(Resource, Debug)]
+struct ChestReveal {
+    rewards: Vec<Choice>,
+    shown: usize,
+    timer: Timer,
+}
+
+#[derive(Component, Debug)]
+struct RewardList;
+
+fn drop_chests(
+    mut commands: Commands,
+    mut events: EventReader<EntityDied>,
+    sprites: Res<SpriteAssets>,
+) {
+    for death in events.read() {
+        if death.kind != DeathKind::Elite {
+            continue;
+        }
+
+        let transform = Transform::from_translation(death.position.extend(CHEST_Z));
+
+        commands.spawn((
+            Chest,
+            RunEntity,
+            sprites.tile(CHEST_SPRITE, transform),
+            Collider::circle(CHEST_RADIUS).with_layers(Layer::PICKUP, Layer::PLAYER),
+        ));
+    }
+}
+
+/// Opens chests the player touches, granting their upgrades right away. The chest
+/// screen then shows what they were.
+fn collect_chests(
+    mut commands: Commands,
+    mut events: EventReader<CollisionStarted>,
+    mut upgrades: PlayerUpgrades,
+    player_q: Query<&Stats, With<Player>>,
+    chests_q: Query<(), With<Chest>>,
+    config: Res<GameplayConfig>,
+) {
+    let Ok(stats) = player_q.get_single() else {
+        return;
+    };
+
+    let luck = stats.get(Stat::Luck);
+    let mut rewards = vec![];
+
+    for collision in events.read() {
+        if !chests_q.contains(collision.collided_with) {
+            continue;
+        }
+
+        commands.entity(collision.collided_with).despawn_recursive();
+
+        // Luck makes the rolls granting several upgrades more likely.
+        let Ok(roll) = config
+            .chest
+            .rolls
+            .choose_weighted(&mut rand::thread_rng(), |roll| {
+                if roll.upgrades > 1 {
+                    roll.weight * luck
+                } else {
+                    roll.weight
+                }
+            })
+        else {
+            continue;
+        };
+
+        rewards.extend(upgrades.open_chest(roll.upgrades));
+    }
+
+    if !rewards.is_empty() {
+        commands.insert_resource(ChestReveal {
+            rewards,
+            shown: 0,
+            timer: Timer::from_seconds(REVEAL_INTERVAL, TimerMode::Repeating),
+        });
+    }
+}
+
+/// Switches to the chest screen. Waits for the player to be back in the game when a
+/// level up came first.
+fn show_chest(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::OpenChest);
+}
+
+fn discard_chest_reveal(mut commands: Commands) {
+    commands.remove_resource::<ChestReveal>();
+}
+
+fn spawn_chest_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            StateScoped(GameState::OpenChest),
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.),
+                    ..default()
+                },
+                background_color: Color::srgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+        ))
+        .with_children(|screen| {
+            screen.spawn(TextBundle::from_section(
+                "Treasure!",
+                TextStyle {
+                    font_size: 48.,
+                    ..default()
+                },
+            ));
+            screen.spawn((
+                RewardList,
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(12.),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// Adds the rewards to the chest screen one at a time. Enter shows the rest at once,
+/// then returns to the game.
+fn reveal_rewards(
+    mut commands: Commands,
+    mut reveal: ResMut<ChestReveal>,
+    list_q: Query<Entity, With<RewardList>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Ok(list) = list_q.get_single() else {
+        return;
+    };
+
+    let skip = keyboard_input.just_pressed(KeyCode::Enter);
+
+    if reveal.shown == reveal.rewards.len() {
+        if skip {
+            commands.remove_resource::<ChestReveal>();
+            next_state.set(GameState::Playing);
+        }
+
+        return;
+    }
+
+    reveal.timer.tick(time.delta());
+
+    let count = if skip {
+        reveal.rewards.len() - reveal.shown
+    } else {
+        reveal.timer.times_finished_this_tick() as usize
+    };
+
+    for _ in 0..count.min(reveal.rewards.len() - reveal.shown) {
+        let reward = &reveal.rewards[reveal.shown];
+
+        let text = commands
+            .spawn(TextBundle::from_sections([
+                TextSection::new(
+                    format!("{}\n", reward.title),
+                    TextStyle {
+                        font_size: 24.,
+                        ..default()
+                    },
+                ),
+                TextSection::new(
+                    reward.description.clone(),
+                    TextStyle {
+                        font_size: 16.,
+                        color: Color::srgb(0.8, 0.8, 0.8),
+                        ..default()
+                    },
+                ),
+            ]))
+            .id();
+
+        commands.entity(list).add_child(text);
+        reveal.shown += 1;
+    }
+
+    if reveal.shown == reveal.rewards.len() {
+        let hint = commands
+            .spawn(TextBundle::from_section(
+                "Press Enter to continue",
+                TextStyle {
+                    font_size: 20.,
+                    ..default()
+                },
+            ))
+            .id();
+
+        commands.entity(list).add_child(hint);
+    }
+}
@@ -1,50 +1,125 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle, utils::hashbrown::HashMap};
+use std::ops::BitOr;
+
+use bevy::{
+    ecs::system::SystemParam, prelude::*, sprite::MaterialMesh2dBundle, utils::hashbrown::HashMap,
+};
 
 use crate::{
-    ghost::Ghost,
-    player::{Dagger, Player},
-    schedule::InGame,
+    broadphase::SpatialHash, enemy::Enemy, health::DamageType, narrowphase::WorldShape,
+    player::Player, schedule::InGame, weapons::Projectile,
 };
 
 pub struct CollisionPlugin;
 
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, detect_collisions.in_set(InGame::CollisionDetection))
-            .add_systems(Update, setup_collision_gizmos)
+        app.init_resource::<Broadphase>()
             .add_systems(
-                Update,
+                FixedUpdate,
                 (
-                    handle_collisions::<Player>,
-                    handle_collisions::<Ghost>,
-                    handle_collisions::<Dagger>,
+                    detect_collisions,
+                    (
+                        handle_collisions::<Player>,
+                        handle_collisions::<Enemy>,
+                        handle_collisions::<Projectile>,
+                    ),
                 )
-                    .in_set(InGame::ProcessCombat),
+                    .chain()
+                    .in_set(InGame::CollisionDetection),
             )
-            .add_event::<CollisionEvent>();
+            .add_systems(Update, (setup_collision_gizmos, update_collision_gizmos))
+            .add_event::<CollisionStarted>()
+            .add_event::<CollisionOngoing>()
+            .add_event::<CollisionEnded>();
     }
 }
 
+const BROADPHASE_CELL_SIZE: f32 = 32.;
+const GIZMO_COLOR: Color = Color::srgba(0.196, 0.804, 0.196, 0.5);
+const GIZMO_COLLIDING_COLOR: Color = Color::srgba(0.863, 0.078, 0.235, 0.5);
+
+/// Sent on the first frame `entity` overlaps `collided_with`.
 #[derive(Event, Debug)]
-pub struct CollisionEvent {
+pub struct CollisionStarted {
     pub entity: Entity,
     pub collided_with: Entity,
 }
 
-impl CollisionEvent {
-    fn new(entity: Entity, collided_with: Entity) -> Self {
-        Self {
-            entity,
-            collided_with,
+/// Sent on every following frame the two entities keep overlapping.
+#[derive(Event, Debug)]
+pub struct CollisionOngoing {
+    pub entity: Entity,
+    pub collided_with: Entity,
+}
+
+/// Sent once the two entities stop overlapping, or when `collided_with` is despawned.
+#[derive(Event, Debug)]
+pub struct CollisionEnded {
+    pub entity: Entity,
+    pub collided_with: Entity,
+}
+
+/// Bitset of collision layers. Two colliders only collide when each one's
+/// `layer` is contained in the other one's `mask`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layer(u32);
+
+impl Layer {
+    pub const PLAYER: Self = Self(1 << 0);
+    pub const ENEMY: Self = Self(1 << 1);
+    pub const PLAYER_PROJECTILE: Self = Self(1 << 2);
+    pub const WALL: Self = Self(1 << 3);
+    pub const PICKUP: Self = Self(1 << 4);
+    pub const ENEMY_PROJECTILE: Self = Self(1 << 5);
+    pub const ALL: Self = Self(u32::MAX);
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for Layer {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Shape of a collider in the entity's local space. Shapes rotate with the
+/// entity's `Transform`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Box(Vec2),
+    Circle(f32),
+    /// Capsule along the local Y axis; `half_length` excludes the rounded caps.
+    Capsule {
+        radius: f32,
+        half_length: f32,
+    },
+}
+
+impl Shape {
+    fn mesh(&self) -> Mesh {
+        match *self {
+            Shape::Box(size) => Rectangle::from_size(size).into(),
+            Shape::Circle(radius) => Circle::new(radius).into(),
+            Shape::Capsule {
+                radius,
+                half_length,
+            } => Capsule2d::new(radius, half_length * 2.).into(),
         }
     }
 }
 
 #[derive(Component, Debug)]
 pub struct Collider {
-    pub size: Vec2,
+    pub shape: Shape,
     pub offset: Vec2,
+    pub layer: Layer,
+    pub mask: Layer,
     pub collisions: Vec<Entity>,
+    previous_collisions: Vec<Entity>,
 }
 
 impl Default for Collider {
@@ -53,14 +128,155 @@ impl Default for Collider {
     }
 }
 
+/// Opt-in continuous collision detection. The collider is swept from where it was
+/// at the previous detection to its current position, so fast movers can't tunnel
+/// through thin colliders. Its `Collider::collisions` are sorted by time of impact,
+/// earliest hit first.
+#[derive(Component, Debug, Default)]
+pub struct ContinuousCollision {
+    previous_translation: Option<Vec2>,
+}
+
+#[derive(Component, Debug)]
+struct ColliderGizmo;
+
+#[derive(Debug, Clone, Copy)]
+struct Body {
+    entity: Entity,
+    layer: Layer,
+    mask: Layer,
+    shape: WorldShape,
+    /// Distance swept since the previous detection, zero unless continuous.
+    motion: Vec2,
+}
+
+impl Body {
+    fn time_of_impact(&self, other: &Body) -> Option<f32> {
+        if self.motion == Vec2::ZERO && other.motion == Vec2::ZERO {
+            return self.shape.penetration(&other.shape).map(|_| 0.);
+        }
+
+        self.shape.translated(-self.motion).time_of_impact(
+            self.motion - other.motion,
+            &other.shape.translated(-other.motion),
+        )
+    }
+}
+
+/// Spatial hash of every collider, rebuilt each frame by `detect_collisions`.
+#[derive(Resource, Debug)]
+pub struct Broadphase(SpatialHash<Body>);
+
+impl Default for Broadphase {
+    fn default() -> Self {
+        Self(SpatialHash::new(BROADPHASE_CELL_SIZE))
+    }
+}
+
+/// Read-only queries against every collider, as placed by the last `detect_collisions`.
+/// Only colliders whose layer intersects the given `mask` are considered.
+#[derive(SystemParam)]
+pub struct SpatialQuery<'w> {
+    broadphase: Res<'w, Broadphase>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub entity: Entity,
+    pub distance: f32,
+    // Nothing reads where a ray hit yet, only the tests.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub point: Vec2,
+}
+
+impl SpatialQuery<'_> {
+    pub fn raycast(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+        mask: Layer,
+    ) -> Option<RayHit> {
+        let direction = direction.try_normalize()?;
+        let end = origin + direction * max_distance;
+        let mut closest: Option<RayHit> = None;
+
+        self.for_each_candidate(Rect::from_corners(origin, end), mask, |body| {
+            let Some(distance) = body.shape.raycast(origin, direction) else {
+                return;
+            };
+
+            if distance > max_distance {
+                return;
+            }
+
+            if closest.map_or(true, |hit| distance < hit.distance) {
+                closest = Some(RayHit {
+                    entity: body.entity,
+                    distance,
+                    point: origin + direction * distance,
+                });
+            }
+        });
+
+        closest
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn point_query(&self, point: Vec2, mask: Layer) -> Vec<Entity> {
+        self.shape_query(WorldShape::circle(point, 0.), mask)
+    }
+
+    pub fn aabb_query(&self, rect: Rect, mask: Layer) -> Vec<Entity> {
+        self.shape_query(
+            WorldShape::oriented_box(rect.center(), rect.half_size(), Vec2::X),
+            mask,
+        )
+    }
+
+    pub fn circle_query(&self, center: Vec2, radius: f32, mask: Layer) -> Vec<Entity> {
+        self.shape_query(WorldShape::circle(center, radius), mask)
+    }
+
+    fn shape_query(&self, shape: WorldShape, mask: Layer) -> Vec<Entity> {
+        let mut entities = vec![];
+
+        self.for_each_candidate(shape.aabb(), mask, |body| {
+            if shape.penetration(&body.shape).is_some() {
+                entities.push(body.entity);
+            }
+        });
+
+        entities
+    }
+
+    fn for_each_candidate(&self, rect: Rect, mask: Layer, mut f: impl FnMut(&Body)) {
+        // Points and axis-aligned rays have empty bounds, which never overlap anything.
+        self.broadphase.0.query_rect(rect.inflate(1.), |body| {
+            if mask.intersects(body.layer) {
+                f(&body);
+            }
+        });
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct CollisionDamage {
     pub amount: u32,
+    pub kind: DamageType,
 }
 
 impl CollisionDamage {
     pub fn new(amount: u32) -> Self {
-        Self { amount }
+        Self {
+            amount,
+            kind: DamageType::Physical,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: DamageType) -> Self {
+        self.kind = kind;
+        self
     }
 }
 
@@ -70,49 +286,158 @@ impl Collider {
     }
 
     pub fn with_size_and_offset(size: Vec2, offset: Vec2) -> Self {
+        Self::with_shape(Shape::Box(size)).with_offset(offset)
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Self::with_shape(Shape::Circle(radius))
+    }
+
+    pub fn capsule(radius: f32, half_length: f32) -> Self {
+        Self::with_shape(Shape::Capsule {
+            radius,
+            half_length,
+        })
+    }
+
+    pub fn with_shape(shape: Shape) -> Self {
         Self {
-            size,
-            offset,
+            shape,
+            offset: Vec2::ZERO,
+            layer: Layer::ALL,
+            mask: Layer::ALL,
             collisions: vec![],
+            previous_collisions: vec![],
         }
     }
 
-    pub fn to_rect_at(&self, transform: &Transform) -> Rect {
-        Rect::from_center_size(transform.translation.truncate() + self.offset, self.size)
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_layers(mut self, layer: Layer, mask: Layer) -> Self {
+        self.layer = layer;
+        self.mask = mask;
+        self
+    }
+
+    pub fn shape_at(&self, transform: &Transform) -> WorldShape {
+        let axis_x = (transform.rotation * Vec3::X)
+            .truncate()
+            .normalize_or_zero();
+        let axis_y = axis_x.perp();
+        let center =
+            transform.translation.truncate() + axis_x * self.offset.x + axis_y * self.offset.y;
+
+        match self.shape {
+            Shape::Box(size) => WorldShape::oriented_box(center, size / 2., axis_x),
+            Shape::Circle(radius) => WorldShape::circle(center, radius),
+            Shape::Capsule {
+                radius,
+                half_length,
+            } => WorldShape::capsule(
+                center - axis_y * half_length,
+                center + axis_y * half_length,
+                radius,
+            ),
+        }
     }
 }
 
-fn detect_collisions(mut colliders_q: Query<(Entity, &Transform, &mut Collider)>) {
-    let mut collisions: HashMap<Entity, Vec<Entity>> = HashMap::new();
+pub fn detect_collisions(
+    mut broadphase: ResMut<Broadphase>,
+    mut colliders_q: Query<(
+        Entity,
+        &Transform,
+        &mut Collider,
+        Option<&mut ContinuousCollision>,
+    )>,
+) {
+    broadphase.0.clear();
 
-    for (entity_a, transform_a, collider_a) in colliders_q.iter() {
-        let rect_a = collider_a.to_rect_at(transform_a);
+    for (entity, transform, collider, continuous) in colliders_q.iter() {
+        let shape = collider.shape_at(transform);
+        let translation = transform.translation.truncate();
 
-        for (entity_b, transform_b, collider_b) in colliders_q.iter() {
-            if entity_a == entity_b {
-                continue;
-            }
+        let motion = continuous
+            .and_then(|continuous| continuous.previous_translation)
+            .map_or(Vec2::ZERO, |previous| translation - previous);
 
-            let rect_b = collider_b.to_rect_at(transform_b);
+        broadphase.0.insert(
+            shape.aabb().union(shape.translated(-motion).aabb()),
+            Body {
+                entity,
+                layer: collider.layer,
+                mask: collider.mask,
+                shape,
+                motion,
+            },
+        );
+    }
 
-            if !rect_a.intersect(rect_b).is_empty() {
-                collisions.entry(entity_a).or_default().push(entity_b);
-            }
+    let mut collisions: HashMap<Entity, Vec<(Entity, f32)>> = HashMap::new();
+
+    broadphase.0.for_each_overlapping_pair(|a, b| {
+        if !a.mask.intersects(b.layer) || !b.mask.intersects(a.layer) {
+            return;
         }
-    }
 
-    for (entity_a, _, mut collider_a) in colliders_q.iter_mut() {
-        collider_a.collisions = collisions.remove(&entity_a).unwrap_or_default();
+        let Some(time) = a.time_of_impact(&b) else {
+            return;
+        };
+
+        collisions
+            .entry(a.entity)
+            .or_default()
+            .push((b.entity, time));
+        collisions
+            .entry(b.entity)
+            .or_default()
+            .push((a.entity, time));
+    });
+
+    for (entity_a, transform_a, mut collider_a, continuous_a) in colliders_q.iter_mut() {
+        let mut current = collisions.remove(&entity_a).unwrap_or_default();
+        current.sort_by(|(_, time_a), (_, time_b)| time_a.total_cmp(time_b));
+
+        let current = current.into_iter().map(|(entity, _)| entity).collect();
+        collider_a.previous_collisions = std::mem::replace(&mut collider_a.collisions, current);
+
+        if let Some(mut continuous_a) = continuous_a {
+            continuous_a.previous_translation = Some(transform_a.translation.truncate());
+        }
     }
 }
 
-fn handle_collisions<T: Component>(
-    mut events: EventWriter<CollisionEvent>,
+pub fn handle_collisions<T: Component>(
+    mut started_events: EventWriter<CollisionStarted>,
+    mut ongoing_events: EventWriter<CollisionOngoing>,
+    mut ended_events: EventWriter<CollisionEnded>,
     entities_q: Query<(Entity, &Collider), With<T>>,
 ) {
     for (entity, collider) in entities_q.iter() {
-        for collided_with in collider.collisions.iter() {
-            events.send(CollisionEvent::new(entity, *collided_with));
+        for &collided_with in collider.collisions.iter() {
+            if collider.previous_collisions.contains(&collided_with) {
+                ongoing_events.send(CollisionOngoing {
+                    entity,
+                    collided_with,
+                });
+            } else {
+                started_events.send(CollisionStarted {
+                    entity,
+                    collided_with,
+                });
+            }
+        }
+
+        for &collided_with in collider.previous_collisions.iter() {
+            if !collider.collisions.contains(&collided_with) {
+                ended_events.send(CollisionEnded {
+                    entity,
+                    collided_with,
+                });
+            }
         }
     }
 }
@@ -127,16 +452,161 @@ fn setup_collision_gizmos(
         return;
     }
 
-    let color = Color::srgba(0.196, 0.804, 0.196, 0.5);
-
     for (entity, collider) in entites_q.iter() {
         commands.entity(entity).with_children(|parent| {
-            parent.spawn(MaterialMesh2dBundle {
-                mesh: meshes.add(Rectangle::from_size(collider.size)).into(),
-                material: materials.add(ColorMaterial::from(color)),
-                transform: Transform::from_translation(collider.offset.extend(100.)),
-                ..default()
-            });
+            parent.spawn((
+                ColliderGizmo,
+                MaterialMesh2dBundle {
+                    mesh: meshes.add(collider.shape.mesh()).into(),
+                    material: materials.add(ColorMaterial::from(GIZMO_COLOR)),
+                    transform: Transform::from_translation(collider.offset.extend(100.)),
+                    ..default()
+                },
+            ));
+        });
+    }
+}
+
+fn update_collision_gizmos(
+    mut started_events: EventReader<CollisionStarted>,
+    mut ended_events: EventReader<CollisionEnded>,
+    colliders_q: Query<(&Collider, &Children)>,
+    gizmos_q: Query<&Handle<ColorMaterial>, With<ColliderGizmo>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !cfg!(feature = "gizmos") {
+        return;
+    }
+
+    let changed = started_events
+        .read()
+        .flat_map(|event| [event.entity, event.collided_with])
+        .chain(
+            ended_events
+                .read()
+                .flat_map(|event| [event.entity, event.collided_with]),
+        );
+
+    for entity in changed {
+        let Ok((collider, children)) = colliders_q.get(entity) else {
+            continue;
+        };
+
+        let color = if collider.collisions.is_empty() {
+            GIZMO_COLOR
+        } else {
+            GIZMO_COLLIDING_COLOR
+        };
+
+        for child in children.iter() {
+            let Ok(material) = gizmos_q.get(*child) else {
+                continue;
+            };
+
+            if let Some(material) = materials.get_mut(material) {
+                material.color = color;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    /// A world whose broadphase holds a wall box, an enemy circle and the player capsule.
+    fn world() -> (World, [Entity; 3]) {
+        let mut world = World::new();
+        world.init_resource::<Broadphase>();
+
+        let wall = world
+            .spawn((
+                Collider::new(Vec2::new(20., 100.)).with_layers(Layer::WALL, Layer::ALL),
+                Transform::from_xyz(50., 0., 0.),
+            ))
+            .id();
+        let enemy = world
+            .spawn((
+                Collider::circle(5.).with_layers(Layer::ENEMY, Layer::ALL),
+                Transform::from_xyz(20., 0., 0.),
+            ))
+            .id();
+        let player = world
+            .spawn((
+                Collider::capsule(4., 2.).with_layers(Layer::PLAYER, Layer::ALL),
+                Transform::from_xyz(0., 30., 0.),
+            ))
+            .id();
+
+        world.run_system_once(detect_collisions);
+
+        (world, [wall, enemy, player])
+    }
+
+    #[test]
+    fn raycast_reports_the_nearest_hit_of_the_masked_layers() {
+        let (mut world, [wall, enemy, _]) = world();
+
+        let hit = world
+            .run_system_once(|query: SpatialQuery| {
+                query.raycast(Vec2::ZERO, Vec2::X, 100., Layer::WALL | Layer::ENEMY)
+            })
+            .unwrap();
+        assert_eq!(hit.entity, enemy);
+        assert!((hit.distance - 15.).abs() < 1e-3);
+        assert!(hit.point.distance(Vec2::new(15., 0.)) < 1e-3);
+
+        let hit = world
+            .run_system_once(|query: SpatialQuery| {
+                query.raycast(Vec2::ZERO, Vec2::X, 100., Layer::WALL)
+            })
+            .unwrap();
+        assert_eq!(hit.entity, wall);
+        assert!((hit.distance - 40.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn raycast_stops_at_max_distance() {
+        let (mut world, _) = world();
+
+        let hit = world.run_system_once(|query: SpatialQuery| {
+            query.raycast(Vec2::ZERO, Vec2::X, 10., Layer::ALL)
+        });
+
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn raycast_hits_the_side_of_an_upright_capsule() {
+        let (mut world, [_, _, player]) = world();
+
+        let hit = world
+            .run_system_once(|query: SpatialQuery| {
+                query.raycast(Vec2::new(-20., 30.), Vec2::X, 100., Layer::PLAYER)
+            })
+            .unwrap();
+
+        assert_eq!(hit.entity, player);
+        assert!((hit.distance - 16.).abs() < 1e-3, "got {}", hit.distance);
+    }
+
+    #[test]
+    fn overlap_queries_filter_by_layer() {
+        let (mut world, [wall, enemy, player]) = world();
+
+        let (points, circles, rects) = world.run_system_once(|query: SpatialQuery| {
+            (
+                query.point_query(Vec2::new(50., 40.), Layer::ALL),
+                query.circle_query(Vec2::new(10., 15.), 20., Layer::ENEMY | Layer::PLAYER),
+                query.aabb_query(Rect::new(-5., -5., 55., 5.), Layer::WALL),
+            )
         });
+
+        assert_eq!(points, vec![wall]);
+        assert!(circles.contains(&enemy) && circles.contains(&player));
+        assert_eq!(circles.len(), 2);
+        assert_eq!(rects, vec![wall]);
     }
 }
@@ -1,22 +1,27 @@
 use bevy::{prelude::*, utils::HashMap};
 
-use crate::{collision::CollisionEvent, ghost::Ghost, player::Player, schedule::InGame};
+use crate::{
+    config::GameplayConfig,
+    enemy::Enemy,
+    health::{DamageDealt, DamageSystems},
+    player::Player,
+    schedule::InGame,
+};
 
 pub struct CombatPlugin;
 
 impl Plugin for CombatPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, apply_knockback.in_set(InGame::EntityUpdate))
+        app.add_systems(FixedUpdate, apply_knockback.in_set(InGame::EntityUpdate))
             .add_systems(
-                Update,
-                knockback_collisions::<Player, Ghost>.in_set(InGame::ProcessCombat),
+                FixedUpdate,
+                knockback_on_damage::<Player, Enemy>
+                    .after(DamageSystems)
+                    .in_set(InGame::ProcessCombat),
             );
     }
 }
 
-const KNOCK_BACK_DISTANCE: f32 = 16.;
-const KNOCK_BACK_DURATION: f32 = 0.1;
-
 #[derive(Component, Debug)]
 pub struct KnockBack {
     pub displacement: Vec3,
@@ -40,31 +45,33 @@ fn apply_knockback(
     }
 }
 
-fn knockback_collisions<T: Component, C: Component>(
+/// Pushes `T` away from the `C` that damaged it.
+fn knockback_on_damage<T: Component, C: Component>(
     mut commands: Commands,
-    mut events: EventReader<CollisionEvent>,
+    mut events: EventReader<DamageDealt>,
     receivers_q: Query<&Transform, (With<T>, Without<KnockBack>)>,
     colliders_q: Query<&Transform, With<C>>,
+    config: Res<GameplayConfig>,
 ) {
     let mut knockbacks: HashMap<Entity, Vec3> = HashMap::new();
 
     for event in events.read() {
-        let Ok(receiver_transform) = receivers_q.get(event.entity) else {
+        let Ok(receiver_transform) = receivers_q.get(event.target) else {
             continue;
         };
 
-        let Ok(collider_transform) = colliders_q.get(event.collided_with) else {
+        let Ok(collider_transform) = colliders_q.get(event.source) else {
             continue;
         };
 
-        let direction = knockbacks.entry(event.entity).or_default();
+        let direction = knockbacks.entry(event.target).or_default();
         *direction += receiver_transform.translation - collider_transform.translation;
     }
 
     for (entity, direction) in knockbacks {
         commands.entity(entity).insert(KnockBack {
-            displacement: direction.normalize_or_zero() * KNOCK_BACK_DISTANCE,
-            duration: Timer::from_seconds(KNOCK_BACK_DURATION, TimerMode::Once),
+            displacement: direction.normalize_or_zero() * config.combat.knock_back_distance,
+            duration: Timer::from_seconds(config.combat.knock_back_duration, TimerMode::Once),
         });
     }
 }
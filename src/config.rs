@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    asset_loader::{RonAsset, RonResourcePlugin},
+    stats::Stat,
+};
+
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonResourcePlugin::<GameplayConfig>::new(CONFIG_PATH));
+    }
+}
+
+const CONFIG_PATH: &str = "gameplay.config.ron";
+
+/// Balance values loaded from `assets/gameplay.config.ron`.
+#[derive(Asset, Resource, TypePath, Debug, Clone, Deserialize)]
+pub struct GameplayConfig {
+    pub player: PlayerConfig,
+    pub enemy: EnemyConfig,
+    pub combat: CombatConfig,
+    pub experience: ExperienceConfig,
+    pub elite: EliteConfig,
+    pub chest: ChestConfig,
+    pub healing: HealingConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayerConfig {
+    /// Base stats of the player. Stats left out use their default.
+    pub stats: HashMap<Stat, f32>,
+    /// Seconds the player is invulnerable for after being hit.
+    pub invulnerability: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyConfig {
+    pub separation_strength: f32,
+    pub separation_neighbours: usize,
+    /// Seconds a killed enemy fades out for.
+    pub death_animation: f32,
+}
+
+/// Experience needed for level `n + 1` is `first_level + level_increment * (n - 1)`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExperienceConfig {
+    pub first_level: u32,
+    pub level_increment: u32,
+    pub gem_speed: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EliteConfig {
+    /// Multiplier of the archetype's health.
+    pub health: f32,
+    /// Multiplier of the archetype's sprite and collider size.
+    pub size: f32,
+    /// Red, green and blue the sprite is multiplied by.
+    pub tint: (f32, f32, f32),
+    pub shield: ShieldConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShieldConfig {
+    pub capacity: u32,
+    /// Seconds without being hit before the shield recharges.
+    pub recharge_delay: f32,
+    /// Points recharged per second.
+    pub recharge_rate: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChestConfig {
+    /// How many upgrades a chest can grant, with their weights. Weights of rolls
+    /// granting more than one upgrade are multiplied by the player's luck.
+    pub rolls: Vec<ChestRoll>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChestRoll {
+    pub upgrades: u32,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealingConfig {
+    /// Health restored by a potion.
+    pub potion_amount: u32,
+    /// Chance for an enemy to drop a potion when it dies.
+    pub drop_chance: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CombatConfig {
+    pub knock_back_distance: f32,
+    /// Seconds a knock back takes.
+    pub knock_back_duration: f32,
+}
+
+impl RonAsset for GameplayConfig {
+    const EXTENSIONS: &'static [&'static str] = &["config.ron"];
+
+    fn validate(&self) -> Result<(), String> {
+        let checks = [
+            (
+                self.player.stats.get(&Stat::MaxHealth) >= Some(&1.),
+                "player.stats must have a MaxHealth of at least 1",
+            ),
+            (
+                self.player.stats.values().all(|value| *value >= 0.),
+                "player.stats must not be negative",
+            ),
+            (
+                self.player.invulnerability >= 0.,
+                "player.invulnerability must not be negative",
+            ),
+            (
+                self.enemy.separation_strength >= 0.,
+                "enemy.separation_strength must not be negative",
+            ),
+            (
+                self.enemy.death_animation >= 0.,
+                "enemy.death_animation must not be negative",
+            ),
+            (
+                self.combat.knock_back_distance >= 0.,
+                "combat.knock_back_distance must not be negative",
+            ),
+            (
+                self.combat.knock_back_duration > 0.,
+                "combat.knock_back_duration must be positive",
+            ),
+            (
+                self.experience.first_level > 0,
+                "experience.first_level must be positive",
+            ),
+            (
+                self.elite.health > 0. && self.elite.size > 0.,
+                "elite.health and elite.size must be positive",
+            ),
+            (
+                self.elite.shield.recharge_delay >= 0. && self.elite.shield.recharge_rate >= 0.,
+                "elite.shield timings must not be negative",
+            ),
+            (
+                !self.chest.rolls.is_empty()
+                    && self
+                        .chest
+                        .rolls
+                        .iter()
+                        .all(|roll| roll.upgrades > 0 && roll.weight > 0.),
+                "chest.rolls must grant upgrades with positive weights",
+            ),
+            (
+                self.healing.potion_amount > 0,
+                "healing.potion_amount must be positive",
+            ),
+            (
+                (0. ..=1.).contains(&self.healing.drop_chance),
+                "healing.drop_chance must be between 0 and 1",
+            ),
+        ];
+
+        let errors: Vec<_> = checks
+            .into_iter()
+            .filter(|(valid, _)| !valid)
+            .map(|(_, error)| error)
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join(", "))
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use std::{collections::HashMap, f32::consts::TAU};
+
+use crate::{
+    asset_loader::{RonAsset, RonResourcePlugin, SpriteAssets},
+    collision::{Collider, CollisionDamage, CollisionStarted, Layer, SpatialQuery},
+    config::GameplayConfig,
+    experience::DropsExperience,
+    healing::DropsHealing,
+    health::{DamageSystems, DamageType, DeathAnimation, Defense, EntityDied, Health, Shield},
+    levels::WallTile,
+    movement::{MovementBundle, Separation, Velocity},
+    navigation::{FlowField, NavGrid, PathFollower},
+    player::Player,
+    schedule::InGame,
+    state::RunEntity,
+    status::{OnHitEffects, StatusEffect, StatusEffects},
+    weapons::Projectile,
+};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use rand::Rng;
+use serde::Deserialize;
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonResourcePlugin::<EnemyRegistry>::new(REGISTRY_PATH))
+            .add_systems(
+                FixedUpdate,
+                (
+                    chase_player,
+                    hunt_player,
+                    charge_player,
+                    keep_distance_and_shoot,
+                )
+                    .in_set(InGame::EntityUpdate),
+            )
+            .add_systems(
+                FixedUpdate,
+                (split_on_death, stop_projectiles_at_walls)
+                    .after(DamageSystems)
+                    .in_set(InGame::ProcessCombat),
+            );
+    }
+}
+
+const REGISTRY_PATH: &str = "enemies.registry.ron";
+const ENEMY_Z: f32 = 100.;
+const ENEMY_PROJECTILE_RADIUS: f32 = 3.;
+/// Shooters stop moving while within this distance of their preferred range.
+const SHOOTER_DISTANCE_TOLERANCE: f32 = 8.;
+const SPLIT_SPREAD: f32 = 6.;
+/// Seconds between two paths a hunter plans to the player.
+const HUNTER_REPATH_INTERVAL: f32 = 0.5;
+
+/// Every enemy archetype by id, loaded from `assets/enemies.registry.ron`.
+#[derive(Asset, Resource, TypePath, Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct EnemyRegistry(HashMap<String, EnemyArchetype>);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyArchetype {
+    /// Index of the sprite in the `SpriteAssets` tilemap.
+    pub sprite: usize,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// Radius of the circle collider, in world units.
+    pub radius: f32,
+    pub health: u32,
+    /// Damage dealt to the player on contact.
+    pub damage: u32,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    #[serde(default)]
+    pub defense: Defense,
+    pub speed: f32,
+    /// Experience dropped on death.
+    #[serde(default = "default_experience")]
+    pub experience: u32,
+    pub behaviour: Behaviour,
+}
+
+fn default_scale() -> f32 {
+    1.
+}
+
+fn default_experience() -> u32 {
+    1
+}
+
+/// How an enemy moves and attacks.
+#[derive(Debug, Clone, Deserialize)]
+pub enum Behaviour {
+    /// Follows the flow field straight to the player. Elites plan their own path.
+    Chase,
+    /// Chases until the player is within `range` and in sight, then stands still for
+    /// `windup` seconds and dashes at where the player was for `dash_duration` seconds.
+    Charger {
+        range: f32,
+        windup: f32,
+        dash_speed: f32,
+        dash_duration: f32,
+        cooldown: f32,
+    },
+    /// Keeps `distance` away from the player and fires at them while in sight.
+    Shooter {
+        distance: f32,
+        fire_interval: f32,
+        projectile: EnemyProjectile,
+    },
+    /// Chases the player and splits into `count` enemies of the `child` archetype on death.
+    Splitter { child: String, count: u32 },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyProjectile {
+    /// Index of the sprite in the `SpriteAssets` tilemap.
+    pub sprite: usize,
+    pub speed: f32,
+    pub damage: u32,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    pub lifetime: f32,
+    /// Status effects applied to the player on hit.
+    #[serde(default)]
+    pub effects: Vec<StatusEffect>,
+}
+
+impl RonAsset for EnemyRegistry {
+    const EXTENSIONS: &'static [&'static str] = &["registry.ron"];
+
+    fn validate(&self) -> Result<(), String> {
+        let mut errors = vec![];
+
+        for (id, archetype) in self.0.iter() {
+            if archetype.health == 0 {
+                errors.push(format!("{id}: health must be positive"));
+            }
+
+            if archetype.radius <= 0. || archetype.scale <= 0. {
+                errors.push(format!("{id}: radius and scale must be positive"));
+            }
+
+            if archetype
+                .defense
+                .resistances
+                .values()
+                .any(|value| *value > 1.)
+            {
+                errors.push(format!("{id}: resistances can't be above 1"));
+            }
+
+            match &archetype.behaviour {
+                Behaviour::Chase => {}
+                Behaviour::Charger {
+                    windup,
+                    dash_duration,
+                    cooldown,
+                    ..
+                } => {
+                    if *windup <= 0. || *dash_duration <= 0. || *cooldown <= 0. {
+                        errors.push(format!("{id}: charger timings must be positive"));
+                    }
+                }
+                Behaviour::Shooter {
+                    fire_interval,
+                    projectile,
+                    ..
+                } => {
+                    if *fire_interval <= 0. || projectile.lifetime <= 0. {
+                        errors.push(format!("{id}: shooter timings must be positive"));
+                    }
+
+                    if !projectile.effects.iter().all(StatusEffect::is_valid) {
+                        errors.push(format!("{id}: projectile effects are invalid"));
+                    }
+                }
+                Behaviour::Splitter { child, .. } => match self.0.get(child) {
+                    None => errors.push(format!("{id}: unknown child archetype {child}")),
+                    // Splitters splitting into splitters could multiply forever.
+                    Some(EnemyArchetype {
+                        behaviour: Behaviour::Splitter { .. },
+                        ..
+                    }) => errors.push(format!("{id}: child {child} can't be a splitter")),
+                    Some(_) => {}
+                },
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            errors.sort();
+            Err(errors.join(", "))
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Enemy {
+    pub speed: f32,
+}
+
+#[derive(Component, Debug)]
+struct Chaser;
+
+/// Chases the player along its own A* path instead of the shared flow field.
+#[derive(Component, Debug)]
+struct Hunter(Timer);
+
+#[derive(Component, Debug)]
+struct Charger {
+    range: f32,
+    windup: f32,
+    dash_speed: f32,
+    dash_duration: f32,
+    cooldown: Timer,
+    state: ChargeState,
+}
+
+#[derive(Debug)]
+enum ChargeState {
+    Chasing,
+    WindingUp(Timer),
+    Dashing(Timer),
+}
+
+#[derive(Component, Debug)]
+struct Shooter {
+    distance: f32,
+    fire_cooldown: Timer,
+    projectile: EnemyProjectile,
+}
+
+/// A tougher, bigger and tinted version of its archetype that drops a chest on death.
+#[derive(Component, Debug)]
+pub struct Elite;
+
+#[derive(Component, Debug)]
+struct Splitter {
+    child: String,
+    count: u32,
+}
+
+/// Spawns enemies from the `EnemyRegistry` by archetype id.
+#[derive(SystemParam)]
+pub struct EnemySpawner<'w, 's> {
+    commands: Commands<'w, 's>,
+    registry: Res<'w, EnemyRegistry>,
+    sprites: Res<'w, SpriteAssets>,
+    config: Res<'w, GameplayConfig>,
+}
+
+impl EnemySpawner<'_, '_> {
+    pub fn archetype(&self, id: &str) -> Option<&EnemyArchetype> {
+        self.registry.0.get(id)
+    }
+
+    pub fn spawn(&mut self, id: &str, position: Vec2) -> Option<Entity> {
+        self.spawn_as(id, position, false)
+    }
+
+    pub fn spawn_elite(&mut self, id: &str, position: Vec2) -> Option<Entity> {
+        self.spawn_as(id, position, true)
+    }
+
+    fn spawn_as(&mut self, id: &str, position: Vec2, elite: bool) -> Option<Entity> {
+        let Some(archetype) = self.registry.0.get(id) else {
+            warn!("Unknown enemy archetype {id}");
+            return None;
+        };
+
+        let elite_config = &self.config.elite;
+        let (size, health) = if elite {
+            (elite_config.size, elite_config.health)
+        } else {
+            (1., 1.)
+        };
+
+        let transform = Transform::from_translation(position.extend(ENEMY_Z))
+            .with_scale(Vec3::splat(archetype.scale * size));
+
+        let mut sprite = self.sprites.tile(archetype.sprite, transform);
+
+        if elite {
+            let (red, green, blue) = elite_config.tint;
+            sprite.0.sprite.color = Color::srgb(red, green, blue);
+        }
+
+        let mut enemy = self.commands.spawn((
+            Enemy {
+                speed: archetype.speed,
+            },
+            DropsExperience(archetype.experience),
+            RunEntity,
+            sprite,
+            Health::new((archetype.health as f32 * health).round() as u32),
+            CollisionDamage::new(archetype.damage).with_kind(archetype.damage_type),
+            archetype.defense.clone(),
+            MovementBundle::default(),
+            Collider::circle(archetype.radius * size).with_layers(
+                Layer::ENEMY,
+                Layer::PLAYER | Layer::PLAYER_PROJECTILE | Layer::WALL,
+            ),
+            Separation {
+                strength: self.config.enemy.separation_strength,
+                max_neighbours: self.config.enemy.separation_neighbours,
+            },
+            DeathAnimation(self.config.enemy.death_animation),
+            DropsHealing(self.config.healing.drop_chance),
+            StatusEffects::default(),
+        ));
+
+        if elite {
+            let shield = &elite_config.shield;
+
+            enemy.insert((
+                Elite,
+                Shield::new(shield.capacity, shield.recharge_delay, shield.recharge_rate),
+            ));
+        }
+
+        match archetype.behaviour.clone() {
+            Behaviour::Chase if elite => {
+                enemy.insert((
+                    Hunter(Timer::from_seconds(
+                        HUNTER_REPATH_INTERVAL,
+                        TimerMode::Repeating,
+                    )),
+                    PathFollower::new(archetype.speed, archetype.radius * size),
+                ));
+            }
+            Behaviour::Chase => {
+                enemy.insert(Chaser);
+            }
+            Behaviour::Charger {
+                range,
+                windup,
+                dash_speed,
+                dash_duration,
+                cooldown,
+            } => {
+                enemy.insert(Charger {
+                    range,
+                    windup,
+                    dash_speed,
+                    dash_duration,
+                    cooldown: Timer::from_seconds(cooldown, TimerMode::Once),
+                    state: ChargeState::Chasing,
+                });
+            }
+            Behaviour::Shooter {
+                distance,
+                fire_interval,
+                projectile,
+            } => {
+                enemy.insert(Shooter {
+                    distance,
+                    fire_cooldown: Timer::from_seconds(fire_interval, TimerMode::Repeating),
+                    projectile,
+                });
+            }
+            Behaviour::Splitter { child, count } => {
+                enemy.insert((Chaser, Splitter { child, count }));
+            }
+        }
+
+        Some(enemy.id())
+    }
+}
+
+/// Direction along the flow field. Enemies outside the level or next to the player
+/// head straight for them.
+fn chase_direction(
+    flow_field: &FlowField,
+    nav_grid: &NavGrid,
+    position: Vec3,
+    player_position: Vec3,
+) -> Vec3 {
+    flow_field
+        .direction_at(nav_grid, position.truncate())
+        .map_or(player_position - position, |direction| direction.extend(0.))
+}
+
+fn chase_player(
+    mut chasers_q: Query<(&mut Velocity, &Transform, &Enemy, &StatusEffects), With<Chaser>>,
+    player_q: Query<&Transform, With<Player>>,
+    nav_grid: Res<NavGrid>,
+    flow_field: Res<FlowField>,
+) {
+    if let Ok(player_transform) = player_q.get_single() {
+        for (mut velocity, transform, enemy, statuses) in chasers_q.iter_mut() {
+            let direction = chase_direction(
+                &flow_field,
+                &nav_grid,
+                transform.translation,
+                player_transform.translation,
+            );
+
+            velocity.change_direction_speed(direction, enemy.speed * statuses.speed_multiplier());
+        }
+    }
+}
+
+fn hunt_player(
+    mut hunters_q: Query<(&mut Hunter, &mut PathFollower, &Enemy, &StatusEffects)>,
+    player_q: Query<&Transform, With<Player>>,
+    time: Res<Time>,
+) {
+    let Ok(player_transform) = player_q.get_single() else {
+        return;
+    };
+
+    for (mut hunter, mut follower, enemy, statuses) in hunters_q.iter_mut() {
+        follower.speed = enemy.speed * statuses.speed_multiplier();
+
+        if hunter.0.tick(time.delta()).just_finished() || follower.is_idle() {
+            follower.go_to(player_transform.translation.truncate());
+        }
+    }
+}
+
+fn charge_player(
+    mut chargers_q: Query<(
+        &mut Charger,
+        &mut Velocity,
+        &Transform,
+        &Enemy,
+        &StatusEffects,
+    )>,
+    player_q: Query<(Entity, &Transform), With<Player>>,
+    spatial_query: SpatialQuery,
+    nav_grid: Res<NavGrid>,
+    flow_field: Res<FlowField>,
+    time: Res<Time>,
+) {
+    let Ok((player, player_transform)) = player_q.get_single() else {
+        return;
+    };
+
+    for (mut charger, mut velocity, transform, enemy, statuses) in chargers_q.iter_mut() {
+        let charger = &mut *charger;
+        let to_player = player_transform.translation - transform.translation;
+
+        // Being frozen or stunned cancels a charge.
+        if statuses.is_disabled() {
+            velocity.change_direction_speed(Vec3::ZERO, 0.);
+            charger.state = ChargeState::Chasing;
+            continue;
+        }
+
+        charger.cooldown.tick(time.delta());
+
+        match &mut charger.state {
+            ChargeState::Chasing => {
+                // The dash needs a straight lane to the player with no wall in between.
+                let lane = spatial_query.raycast(
+                    transform.translation.truncate(),
+                    to_player.truncate(),
+                    charger.range,
+                    Layer::WALL | Layer::PLAYER,
+                );
+                let can_charge =
+                    charger.cooldown.finished() && lane.is_some_and(|hit| hit.entity == player);
+
+                if can_charge {
+                    velocity.change_direction_speed(Vec3::ZERO, 0.);
+                    charger.state = ChargeState::WindingUp(Timer::from_seconds(
+                        charger.windup,
+                        TimerMode::Once,
+                    ));
+                } else {
+                    let direction = chase_direction(
+                        &flow_field,
+                        &nav_grid,
+                        transform.translation,
+                        player_transform.translation,
+                    );
+                    velocity.change_direction_speed(
+                        direction,
+                        enemy.speed * statuses.speed_multiplier(),
+                    );
+                }
+            }
+            ChargeState::WindingUp(windup) => {
+                if windup.tick(time.delta()).finished() {
+                    velocity.change_direction_speed(
+                        to_player,
+                        charger.dash_speed * statuses.speed_multiplier(),
+                    );
+                    charger.state = ChargeState::Dashing(Timer::from_seconds(
+                        charger.dash_duration,
+                        TimerMode::Once,
+                    ));
+                }
+            }
+            ChargeState::Dashing(dash) => {
+                if dash.tick(time.delta()).finished() {
+                    charger.cooldown.reset();
+                    charger.state = ChargeState::Chasing;
+                }
+            }
+        }
+    }
+}
+
+fn keep_distance_and_shoot(
+    mut commands: Commands,
+    mut shooters_q: Query<(
+        &mut Shooter,
+        &mut Velocity,
+        &Transform,
+        &Enemy,
+        &StatusEffects,
+    )>,
+    player_q: Query<&Transform, With<Player>>,
+    nav_grid: Res<NavGrid>,
+    flow_field: Res<FlowField>,
+    sprites: Res<SpriteAssets>,
+    time: Res<Time>,
+) {
+    let Ok(player_transform) = player_q.get_single() else {
+        return;
+    };
+
+    for (mut shooter, mut velocity, transform, enemy, statuses) in shooters_q.iter_mut() {
+        if statuses.is_disabled() {
+            velocity.change_direction_speed(Vec3::ZERO, 0.);
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let to_player = player_transform.translation.truncate() - position;
+        let distance = to_player.length();
+
+        let in_sight = nav_grid.has_line_of_sight(
+            position,
+            player_transform.translation.truncate(),
+            ENEMY_PROJECTILE_RADIUS,
+        );
+
+        let direction = if !in_sight || distance > shooter.distance + SHOOTER_DISTANCE_TOLERANCE {
+            chase_direction(
+                &flow_field,
+                &nav_grid,
+                transform.translation,
+                player_transform.translation,
+            )
+        } else if distance < shooter.distance - SHOOTER_DISTANCE_TOLERANCE {
+            -to_player.extend(0.)
+        } else {
+            Vec3::ZERO
+        };
+
+        velocity.change_direction_speed(direction, enemy.speed * statuses.speed_multiplier());
+
+        shooter.fire_cooldown.tick(time.delta());
+
+        if !in_sight || !shooter.fire_cooldown.just_finished() {
+            continue;
+        }
+
+        let Some(aim) = to_player.try_normalize() else {
+            continue;
+        };
+
+        let projectile = &shooter.projectile;
+        let transform = Transform::from_translation(transform.translation)
+            .with_rotation(Quat::from_rotation_z(Vec2::Y.angle_between(aim)));
+
+        commands.spawn((
+            Projectile::new(0, projectile.lifetime),
+            RunEntity,
+            sprites.tile(projectile.sprite, transform),
+            Collider::circle(ENEMY_PROJECTILE_RADIUS)
+                .with_layers(Layer::ENEMY_PROJECTILE, Layer::PLAYER | Layer::WALL),
+            CollisionDamage::new(projectile.damage).with_kind(projectile.damage_type),
+            OnHitEffects(projectile.effects.clone()),
+            MovementBundle {
+                velocity: Velocity::from_direction_speed(aim.extend(0.), projectile.speed),
+                ..default()
+            },
+        ));
+    }
+}
+
+/// Enemy projectiles break on walls instead of flying through them.
+fn stop_projectiles_at_walls(
+    mut commands: Commands,
+    mut events: EventReader<CollisionStarted>,
+    projectiles_q: Query<(), With<Projectile>>,
+    walls_q: Query<(), With<WallTile>>,
+) {
+    for collision in events.read() {
+        if projectiles_q.contains(collision.entity) && walls_q.contains(collision.collided_with) {
+            commands.entity(collision.entity).despawn_recursive();
+        }
+    }
+}
+
+fn split_on_death(
+    mut events: EventReader<EntityDied>,
+    mut spawner: EnemySpawner,
+    splitters_q: Query<&Splitter>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for death in events.read() {
+        let Ok(splitter) = splitters_q.get(death.entity) else {
+            continue;
+        };
+
+        for _ in 0..splitter.count {
+            let offset = Vec2::from_angle(rng.gen_range(0. ..TAU)) * SPLIT_SPREAD;
+            spawner.spawn(&splitter.child, death.position + offset);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    asset_loader::{RonAsset, RonResourcePlugin},
+    upgrades::{Passives, UpgradePool},
+    weapons::WeaponCatalog,
+};
+
+pub struct EvolutionsPlugin;
+
+impl Plugin for EvolutionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonResourcePlugin::<EvolutionRecipes>::new(RECIPES_PATH))
+            .add_systems(
+                Update,
+                check_recipes.run_if(
+                    resource_exists::<EvolutionRecipes>.and_then(resource_exists::<UpgradePool>),
+                ),
+            );
+    }
+}
+
+const RECIPES_PATH: &str = "evolutions.recipes.ron";
+
+/// Which weapons evolve and how, loaded from `assets/evolutions.recipes.ron`.
+#[derive(Asset, Resource, TypePath, Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct EvolutionRecipes(pub Vec<Recipe>);
+
+/// A `weapon` at max level, held together with `passive`, turns into the `evolved`
+/// weapon when a chest is opened.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Recipe {
+    /// Id of a `WeaponDefinition`.
+    pub weapon: String,
+    /// Id of a `PassiveDefinition` of the `UpgradePool`.
+    pub passive: String,
+    /// Id of the `WeaponDefinition` the weapon turns into.
+    pub evolved: String,
+}
+
+impl RonAsset for EvolutionRecipes {
+    const EXTENSIONS: &'static [&'static str] = &["recipes.ron"];
+
+    fn validate(&self) -> Result<(), String> {
+        let mut errors = vec![];
+        let mut weapons = HashSet::new();
+
+        for recipe in self.0.iter() {
+            if !weapons.insert(&recipe.weapon) {
+                errors.push(format!("{}: weapon has several recipes", recipe.weapon));
+            }
+
+            if recipe.weapon == recipe.evolved {
+                errors.push(format!("{}: weapon evolves into itself", recipe.weapon));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join(", "))
+        }
+    }
+}
+
+impl EvolutionRecipes {
+    /// Whether the weapon `id` can only be gained by evolving.
+    pub fn is_evolved(&self, id: &str) -> bool {
+        self.0.iter().any(|recipe| recipe.evolved == id)
+    }
+
+    /// The recipe evolving the weapon `id` when `passives` holds its passive.
+    pub fn ready(&self, id: &str, passives: &Passives) -> Option<&Recipe> {
+        self.0
+            .iter()
+            .find(|recipe| recipe.weapon == id && passives.level_of(&recipe.passive) > 0)
+    }
+
+    /// Describes the evolutions the weapon or passive `id` is part of.
+    pub fn hint(&self, id: &str, catalog: &WeaponCatalog, pool: &UpgradePool) -> Option<String> {
+        let weapon_name = |id: &str| {
+            catalog
+                .find(id)
+                .map_or(id.to_string(), |(_, definition)| definition.name.clone())
+        };
+
+        let passive_name = |id: &str| {
+            pool.passives
+                .iter()
+                .find(|passive| passive.id == id)
+                .map_or(id.to_string(), |passive| passive.name.clone())
+        };
+
+        let hints: Vec<_> = self
+            .0
+            .iter()
+            .filter_map(|recipe| {
+                let evolved = weapon_name(&recipe.evolved);
+
+                if recipe.weapon == id {
+                    Some(format!(
+                        "Evolves with {} into {evolved}",
+                        passive_name(&recipe.passive)
+                    ))
+                } else if recipe.passive == id {
+                    Some(format!(
+                        "Evolves {} into {evolved}",
+                        weapon_name(&recipe.weapon)
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        (!hints.is_empty()).then(|| hints.join("\n"))
+    }
+}
+
+/// Drops the recipes that refer to unknown weapons or passives whenever the recipes
+/// or the passives are loaded or reloaded, once the weapons have loaded.
+fn check_recipes(
+    mut recipes: ResMut<EvolutionRecipes>,
+    mut pending: Local<bool>,
+    catalog: WeaponCatalog,
+    pool: Res<UpgradePool>,
+) {
+    if recipes.is_changed() || pool.is_changed() {
+        *pending = true;
+    }
+
+    if !*pending || !catalog.is_loaded() {
+        return;
+    }
+
+    *pending = false;
+
+    let is_valid = |recipe: &Recipe| {
+        let unknown: Vec<_> = [
+            (catalog.find(&recipe.weapon).is_none(), &recipe.weapon),
+            (catalog.find(&recipe.evolved).is_none(), &recipe.evolved),
+            (
+                !pool
+                    .passives
+                    .iter()
+                    .any(|passive| passive.id == recipe.passive),
+                &recipe.passive,
+            ),
+        ]
+        .into_iter()
+        .filter(|(missing, _)| *missing)
+        .map(|(_, id)| id.as_str())
+        .collect();
+
+        if !unknown.is_empty() {
+            error!(
+                "Ignoring evolution of {}: unknown {}",
+                recipe.weapon,
+                unknown.join(", ")
+            );
+        }
+
+        unknown.is_empty()
+    };
+
+    // Dropping recipes must not count as a reload, or they would be checked again.
+    recipes.bypass_change_detection().0.retain(is_valid);
+}
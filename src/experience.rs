@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+
+use crate::{
+    asset_loader::SpriteAssets,
+    collision::{Collider, CollisionStarted, Layer, SpatialQuery},
+    config::{ExperienceConfig, GameplayConfig},
+    health::{DamageSystems, EntityDied},
+    movement::{MovementBundle, Velocity},
+    player::Player,
+    schedule::InGame,
+    state::{GameState, RunEntity},
+    stats::{Stat, Stats},
+};
+
+pub struct ExperiencePlugin;
+
+impl Plugin for ExperiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (drop_gems.after(DamageSystems), collect_gems).in_set(InGame::ProcessCombat),
+        )
+        .add_systems(FixedUpdate, attract_gems.in_set(InGame::EntityUpdate));
+    }
+}
+
+const GEM_SPRITE: usize = 128;
+const GEM_SCALE: f32 = 0.75;
+const GEM_RADIUS: f32 = 4.;
+/// Below enemies and projectiles, above the level.
+const GEM_Z: f32 = 50.;
+
+/// Experience dropped as a gem when the entity dies.
+#[derive(Component, Debug)]
+pub struct DropsExperience(pub u32);
+
+#[derive(Component, Debug)]
+pub struct ExperienceGem {
+    amount: u32,
+    /// Set once the player came within the magnet radius; the gem then follows them.
+    attracted: bool,
+}
+
+/// Level and progress of the player.
+#[derive(Component, Debug)]
+pub struct Experience {
+    pub level: u32,
+    /// Experience gathered towards the next level.
+    pub amount: u32,
+    /// Levels gained whose upgrade has not been chosen yet.
+    pub pending_level_ups: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            amount: 0,
+            pending_level_ups: 0,
+        }
+    }
+}
+
+impl Experience {
+    /// Experience needed to go from the current level to the next.
+    pub fn required(&self, config: &ExperienceConfig) -> u32 {
+        config.first_level + config.level_increment * (self.level - 1)
+    }
+
+    fn gain(&mut self, amount: u32, config: &ExperienceConfig) {
+        self.amount += amount;
+
+        while self.amount >= self.required(config) {
+            self.amount -= self.required(config);
+            self.level += 1;
+            self.pending_level_ups += 1;
+        }
+    }
+}
+
+fn drop_gems(
+    mut commands: Commands,
+    mut events: EventReader<EntityDied>,
+    drops_q: Query<&DropsExperience>,
+    sprites: Res<SpriteAssets>,
+) {
+    for death in events.read() {
+        let Ok(drops) = drops_q.get(death.entity) else {
+            continue;
+        };
+
+        if drops.0 == 0 {
+            continue;
+        }
+
+        let transform = Transform::from_translation(death.position.extend(GEM_Z))
+            .with_scale(Vec3::splat(GEM_SCALE));
+
+        commands.spawn((
+            ExperienceGem {
+                amount: drops.0,
+                attracted: false,
+            },
+            RunEntity,
+            sprites.tile(GEM_SPRITE, transform),
+            Collider::circle(GEM_RADIUS).with_layers(Layer::PICKUP, Layer::PLAYER),
+            MovementBundle::default(),
+        ));
+    }
+}
+
+fn collect_gems(
+    mut commands: Commands,
+    mut events: EventReader<CollisionStarted>,
+    mut player_q: Query<&mut Experience, With<Player>>,
+    gems_q: Query<&ExperienceGem>,
+    config: Res<GameplayConfig>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Ok(mut experience) = player_q.get_single_mut() else {
+        return;
+    };
+
+    for collision in events.read() {
+        let Ok(gem) = gems_q.get(collision.collided_with) else {
+            continue;
+        };
+
+        experience.gain(gem.amount, &config.experience);
+        commands.entity(collision.collided_with).despawn_recursive();
+    }
+
+    if experience.pending_level_ups > 0 {
+        next_state.set(GameState::LevelUp);
+    }
+}
+
+fn attract_gems(
+    mut gems_q: Query<(&mut ExperienceGem, &mut Velocity, &Transform)>,
+    player_q: Query<(&Transform, &Stats), With<Player>>,
+    spatial_query: SpatialQuery,
+    config: Res<GameplayConfig>,
+) {
+    let Ok((player_transform, stats)) = player_q.get_single() else {
+        return;
+    };
+
+    let player = player_transform.translation.truncate();
+
+    for entity in spatial_query.circle_query(player, stats.get(Stat::Magnet), Layer::PICKUP) {
+        if let Ok((mut gem, _, _)) = gems_q.get_mut(entity) {
+            gem.attracted = true;
+        }
+    }
+
+    for (gem, mut velocity, transform) in gems_q.iter_mut() {
+        if !gem.attracted {
+            continue;
+        }
+
+        let direction = (player - transform.translation.truncate()).extend(0.);
+        velocity.change_direction_speed(direction, config.experience.gem_speed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gain_carries_over_across_several_level_ups() {
+        let config = ExperienceConfig {
+            first_level: 5,
+            level_increment: 10,
+            gem_speed: 0.,
+        };
+        let mut experience = Experience::default();
+
+        experience.gain(4, &config);
+        assert_eq!((experience.level, experience.amount), (1, 4));
+
+        // 5 to reach level 2, 15 to reach 3, 25 to reach 4, then 3 left over.
+        experience.gain(44, &config);
+        assert_eq!(experience.level, 4);
+        assert_eq!(experience.amount, 3);
+        assert_eq!(experience.pending_level_ups, 3);
+        assert_eq!(experience.required(&config), 35);
+    }
+}
@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    asset_loader::SpriteAssets,
+    collision::{Collider, CollisionStarted, Layer},
+    config::GameplayConfig,
+    health::{DamageSystems, EntityDied, Heal},
+    schedule::InGame,
+    state::RunEntity,
+};
+
+pub struct HealingPlugin;
+
+impl Plugin for HealingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (drop_potions.after(DamageSystems), collect_potions).in_set(InGame::ProcessCombat),
+        );
+    }
+}
+
+const POTION_SPRITE: usize = 115;
+const POTION_RADIUS: f32 = 4.;
+/// Below enemies and projectiles, above the level.
+const POTION_Z: f32 = 50.;
+
+/// Chance for the entity to drop a healing potion when it dies.
+#[derive(Component, Debug)]
+pub struct DropsHealing(pub f32);
+
+#[derive(Component, Debug)]
+struct HealingPotion;
+
+fn drop_potions(
+    mut commands: Commands,
+    mut events: EventReader<EntityDied>,
+    drops_q: Query<&DropsHealing>,
+    sprites: Res<SpriteAssets>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for death in events.read() {
+        let Ok(drops) = drops_q.get(death.entity) else {
+            continue;
+        };
+
+        if !rng.gen_bool(drops.0.clamp(0., 1.) as f64) {
+            continue;
+        }
+
+        let transform = Transform::from_translation(death.position.extend(POTION_Z));
+
+        commands.spawn((
+            HealingPotion,
+            RunEntity,
+            sprites.tile(POTION_SPRITE, transform),
+            Collider::circle(POTION_RADIUS).with_layers(Layer::PICKUP, Layer::PLAYER),
+        ));
+    }
+}
+
+fn collect_potions(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionStarted>,
+    mut heals: EventWriter<Heal>,
+    potions_q: Query<(), With<HealingPotion>>,
+    config: Res<GameplayConfig>,
+) {
+    for collision in collisions.read() {
+        if !potions_q.contains(collision.collided_with) {
+            continue;
+        }
+
+        heals.send(Heal {
+            entity: collision.entity,
+            amount: config.healing.potion_amount,
+        });
+        commands.entity(collision.collided_with).despawn_recursive();
+    }
+}
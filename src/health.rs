@@ -1,35 +1,187 @@
+use std::collections::HashMap;
+
 use crate::{
-    collision::{CollisionDamage, CollisionEvent},
-    ghost::Ghost,
-    player::{Dagger, Player},
+    collision::{Collider, CollisionDamage, CollisionOngoing, CollisionStarted},
+    enemy::{Elite, Enemy},
+    movement::Velocity,
+    player::Player,
     schedule::InGame,
+    stats::{Stat, Stats},
+    weapons::Projectile,
 };
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::Deserialize;
 
 pub struct HealthPlugin;
 
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
-            Update,
+            FixedUpdate,
             (
-                take_damage::<Player, Ghost>,
-                take_damage::<Ghost, Dagger>,
-                despawn_dead_entities,
+                take_damage::<Player>,
+                take_damage::<Enemy>,
+                take_hurt,
+                detect_deaths,
             )
                 .chain()
+                .in_set(DamageSystems)
+                .in_set(InGame::ProcessCombat),
+        )
+        .add_systems(
+            FixedUpdate,
+            log_damage
+                .after(DamageSystems)
                 .in_set(InGame::ProcessCombat),
         )
-        .add_systems(Update, tick_damage_cooldown.in_set(InGame::EntityUpdate));
+        .add_systems(
+            FixedUpdate,
+            (
+                tick_damage_cooldown,
+                apply_max_health,
+                recharge_shields,
+                (regenerate, apply_healing).chain(),
+                despawn_dying,
+            )
+                .in_set(InGame::EntityUpdate),
+        )
+        .add_event::<DamageDealt>()
+        .add_event::<EntityDied>()
+        .add_event::<Hurt>()
+        .add_event::<Heal>();
+    }
+}
+
+/// Seconds an entity with a `DamageCooldown` stays hidden or shown while blinking.
+const BLINK_INTERVAL: f32 = 0.08;
+
+/// Applies collision damage and sends `EntityDied` for entities whose health ran out.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub struct DamageSystems;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Ice,
+    Holy,
+}
+
+/// Flat armor and the fraction of each damage type the entity shrugs off. Negative
+/// resistances are weaknesses.
+#[derive(Component, Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Defense {
+    pub armor: u32,
+    pub resistances: HashMap<DamageType, f32>,
+}
+
+/// Absorbs damage before health does. Recharges `recharge_rate` points per second
+/// once the entity went `recharge_delay` seconds without being hit.
+#[derive(Component, Debug)]
+pub struct Shield {
+    capacity: f32,
+    amount: f32,
+    recharge_rate: f32,
+    recharge_delay: Timer,
+}
+
+impl Shield {
+    pub fn new(capacity: u32, recharge_delay: f32, recharge_rate: f32) -> Self {
+        Self {
+            capacity: capacity as f32,
+            amount: capacity as f32,
+            recharge_rate,
+            recharge_delay: Timer::from_seconds(recharge_delay, TimerMode::Once),
+        }
+    }
+
+    /// Takes what it can of `amount` and returns how much.
+    fn absorb(&mut self, amount: u32) -> u32 {
+        let absorbed = (self.amount.floor() as u32).min(amount);
+
+        self.amount -= absorbed as f32;
+        self.recharge_delay.reset();
+
+        absorbed
     }
 }
 
+/// Sent for every hit that landed, once resistances, armor and shields had their say.
+/// Hits ignored during a `DamageCooldown` send nothing.
+#[derive(Event, Debug)]
+pub struct DamageDealt {
+    pub target: Entity,
+    pub source: Entity,
+    pub kind: DamageType,
+    /// Damage the shield of `target` absorbed.
+    pub absorbed: u32,
+    /// Damage taken off the health of `target`.
+    pub amount: u32,
+    /// Whether `source` touched `target`, rather than hurting it from afar like a
+    /// ticking status effect.
+    pub contact: bool,
+}
+
+/// Sent when the health of `entity` runs out. The entity is despawned once its
+/// `DeathAnimation` finishes, so systems in `InGame::ProcessCombat` that run after
+/// `DamageSystems` can still inspect it.
+#[derive(Event, Debug)]
+pub struct EntityDied {
+    pub entity: Entity,
+    /// What dealt the killing blow. It may have been despawned since.
+    pub killer: Option<Entity>,
+    pub position: Vec2,
+    pub kind: DeathKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathKind {
+    Player,
+    Enemy,
+    Elite,
+    Other,
+}
+
+/// Damage dealt outside of collisions, like a status effect ticking. It goes through
+/// resistances, armor and shields, but ignores and doesn't start a `DamageCooldown`.
+#[derive(Event, Debug)]
+pub struct Hurt {
+    pub entity: Entity,
+    pub source: Entity,
+    pub amount: u32,
+    pub kind: DamageType,
+}
+
+/// Restores `amount` health to `entity`, up to its maximum. Dead entities stay dead.
+#[derive(Event, Debug)]
+pub struct Heal {
+    pub entity: Entity,
+    pub amount: u32,
+}
+
+/// Seconds a dying entity fades out for before it is despawned.
+#[derive(Component, Debug)]
+pub struct DeathAnimation(pub f32);
+
+/// Marks an entity whose health ran out. It no longer moves or collides, and is
+/// despawned when the timer finishes.
+#[derive(Component, Debug)]
+pub struct Dying(Timer);
+
 #[derive(Component, Debug)]
 pub struct Health {
     amount: u32,
+    max: u32,
+    /// Seconds the entity is invulnerable for after being hit.
     pub cooldown: Option<f32>,
+    killer: Option<Entity>,
+    /// Regeneration gathered towards the next whole point of health.
+    regenerated: f32,
 }
 
+/// Invulnerability after a hit. The entity blinks until it runs out.
 #[derive(Component, Debug)]
 pub struct DamageCooldown {
     pub cooldown: Timer,
@@ -47,61 +199,527 @@ impl Health {
     pub fn new(amount: u32) -> Self {
         Self {
             amount,
+            max: amount,
             cooldown: None,
+            killer: None,
+            regenerated: 0.,
         }
     }
 
-    pub fn with_damage_cooldown(amount: u32, cooldown: f32) -> Self {
-        Self {
-            amount,
-            cooldown: Some(cooldown),
+    pub fn is_dead(&self) -> bool {
+        self.amount == 0
+    }
+
+    fn hit(&mut self, amount: u32, source: Entity) {
+        self.amount = self.amount.saturating_sub(amount);
+
+        if self.is_dead() {
+            self.killer = Some(source);
+        }
+    }
+
+    fn heal(&mut self, amount: u32) {
+        if !self.is_dead() {
+            self.amount = self.amount.saturating_add(amount).min(self.max);
         }
     }
+
+    /// Changes the maximum. A raised maximum also heals by the difference.
+    fn set_max(&mut self, max: u32) {
+        let increase = max.saturating_sub(self.max);
+
+        self.max = max;
+        self.amount = self.amount.min(max);
+        self.heal(increase);
+    }
+}
+
+/// For UI showing how much health is left. Nothing shows it yet.
+#[cfg_attr(not(test), allow(dead_code))]
+impl Health {
+    pub fn amount(&self) -> u32 {
+        self.amount
+    }
+
+    pub fn max(&self) -> u32 {
+        self.max
+    }
+
+    /// Share of the maximum left, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.max == 0 {
+            return 0.;
+        }
+
+        self.amount as f32 / self.max as f32
+    }
+}
+
+/// Resistances, armor and shields standing between a hit and the health it takes.
+#[derive(SystemParam)]
+struct DamagePipeline<'w, 's> {
+    defenses_q: Query<'w, 's, (Option<&'static Defense>, Option<&'static Stats>)>,
+    shields_q: Query<'w, 's, &'static mut Shield>,
+    events: EventWriter<'w, DamageDealt>,
+}
+
+impl DamagePipeline<'_, '_> {
+    /// Damage of a hit on `target` left after its resistances, then its armor. Armor
+    /// never reduces a hit below 1 damage, resistances can.
+    fn mitigate(&self, target: Entity, amount: u32, kind: DamageType) -> u32 {
+        let (defense, stats) = self.defenses_q.get(target).unwrap_or((None, None));
+
+        let resistance = defense
+            .and_then(|defense| defense.resistances.get(&kind))
+            .copied()
+            .unwrap_or(0.);
+        let resisted = (amount as f32 * (1. - resistance)).round().max(0.) as u32;
+
+        let armor = defense.map_or(0, |defense| defense.armor)
+            + stats.map_or(0, |stats| stats.get(Stat::Armor).round() as u32);
+
+        resisted.saturating_sub(armor).max(resisted.min(1))
+    }
+
+    /// Lets the shield of `target` absorb what it can of `amount` and takes the rest
+    /// off `health`.
+    fn apply(
+        &mut self,
+        target: Entity,
+        source: Entity,
+        kind: DamageType,
+        amount: u32,
+        contact: bool,
+        health: &mut Health,
+    ) {
+        let absorbed = self
+            .shields_q
+            .get_mut(target)
+            .map_or(0, |mut shield| shield.absorb(amount));
+
+        health.hit(amount - absorbed, source);
+
+        self.events.send(DamageDealt {
+            target,
+            source,
+            kind,
+            absorbed,
+            amount: amount - absorbed,
+            contact,
+        });
+    }
 }
 
-fn take_damage<T: Component, E: Component>(
+/// Applies the damage of everything touching a `T`, as far as the collision layers
+/// let them touch. Entities with a `Health::cooldown` also take damage from what they
+/// keep touching once their cooldown runs out, but only from the strongest of the
+/// hits landing in the same frame, whether from contact or projectiles. Hits fully
+/// resisted don't count.
+fn take_damage<T: Component>(
     mut commands: Commands,
-    mut events: EventReader<CollisionEvent>,
-    mut reciever_q: Query<&mut Health, (With<T>, Without<DamageCooldown>)>,
-    damager_q: Query<&CollisionDamage, With<E>>,
+    mut started_events: EventReader<CollisionStarted>,
+    mut ongoing_events: EventReader<CollisionOngoing>,
+    mut reciever_q: Query<(&mut Health, Has<DamageCooldown>), With<T>>,
+    mut damager_q: Query<(&CollisionDamage, Option<&mut Projectile>, Option<&Collider>)>,
+    mut pipeline: DamagePipeline,
 ) {
-    for collision in events.read() {
-        let Ok(mut health) = reciever_q.get_mut(collision.entity) else {
+    let started = started_events
+        .read()
+        .map(|collision| (collision.entity, collision.collided_with, false));
+    let ongoing = ongoing_events
+        .read()
+        .map(|collision| (collision.entity, collision.collided_with, true));
+
+    let mut hits: Vec<_> = started.chain(ongoing).collect();
+
+    // Events come target by target. A damager's own collisions are sorted by time of
+    // impact, so projectiles spend their hits on the targets they reach first.
+    hits.sort_by_cached_key(|(entity, damager, _)| {
+        damager_q
+            .get(*damager)
+            .ok()
+            .and_then(|(_, _, collider)| collider)
+            .and_then(|collider| {
+                collider
+                    .collisions
+                    .iter()
+                    .position(|collided| collided == entity)
+            })
+    });
+
+    let mut strongest: HashMap<Entity, (u32, Entity, DamageType)> = HashMap::new();
+
+    for (entity, damager, ongoing) in hits {
+        let Ok((mut health, recovering)) = reciever_q.get_mut(entity) else {
+            continue;
+        };
+
+        let Ok((damage, projectile, _)) = damager_q.get_mut(damager) else {
             continue;
         };
 
-        let Ok(damage) = damager_q.get(collision.collided_with) else {
+        if health.is_dead() || (ongoing && health.cooldown.is_none()) {
+            continue;
+        }
+
+        // Projectiles are used up by the first targets they touch, even ones they
+        // can't hurt right now.
+        if let Some(mut projectile) = projectile {
+            let has_hit = if ongoing {
+                !projectile.is_spent()
+            } else {
+                projectile.hit()
+            };
+
+            if !has_hit {
+                continue;
+            }
+        }
+
+        if recovering {
+            continue;
+        }
+
+        let amount = pipeline.mitigate(entity, damage.amount, damage.kind);
+
+        if amount == 0 {
+            continue;
+        }
+
+        if health.cooldown.is_none() {
+            pipeline.apply(entity, damager, damage.kind, amount, true, &mut health);
+            continue;
+        }
+
+        let hit = strongest.entry(entity).or_insert((0, damager, damage.kind));
+
+        if amount > hit.0 {
+            *hit = (amount, damager, damage.kind);
+        }
+    }
+
+    for (entity, (amount, damager, kind)) in strongest {
+        let Ok((mut health, _)) = reciever_q.get_mut(entity) else {
             continue;
         };
 
-        health.amount = health.amount.saturating_sub(damage.amount);
+        pipeline.apply(entity, damager, kind, amount, true, &mut health);
 
-        if let Some(duration) = health.cooldown.as_ref() {
+        if let Some(duration) = health.cooldown {
             commands
-                .entity(collision.entity)
-                .insert(DamageCooldown::new(*duration));
+                .entity(entity)
+                .insert(DamageCooldown::new(duration));
+        }
+    }
+}
+
+fn take_hurt(
+    mut events: EventReader<Hurt>,
+    mut entities_q: Query<&mut Health>,
+    mut pipeline: DamagePipeline,
+) {
+    for hurt in events.read() {
+        let Ok(mut health) = entities_q.get_mut(hurt.entity) else {
+            continue;
+        };
+
+        if health.is_dead() {
+            continue;
+        }
+
+        let amount = pipeline.mitigate(hurt.entity, hurt.amount, hurt.kind);
+
+        if amount == 0 {
+            continue;
+        }
+
+        pipeline.apply(
+            hurt.entity,
+            hurt.source,
+            hurt.kind,
+            amount,
+            false,
+            &mut health,
+        );
+    }
+}
+
+fn detect_deaths(
+    mut commands: Commands,
+    mut events: EventWriter<EntityDied>,
+    entities_q: Query<(Entity, &Health, &Transform, Option<&DeathAnimation>), Without<Dying>>,
+    kinds_q: Query<(Has<Player>, Has<Enemy>, Has<Elite>)>,
+) {
+    for (entity, health, transform, animation) in entities_q.iter() {
+        if !health.is_dead() {
+            continue;
         }
+
+        let kind = match kinds_q.get(entity) {
+            Ok((true, _, _)) => DeathKind::Player,
+            Ok((_, true, true)) => DeathKind::Elite,
+            Ok((_, true, false)) => DeathKind::Enemy,
+            _ => DeathKind::Other,
+        };
+
+        events.send(EntityDied {
+            entity,
+            killer: health.killer,
+            position: transform.translation.truncate(),
+            kind,
+        });
+
+        let duration = animation.map_or(0., |animation| animation.0);
+
+        commands
+            .entity(entity)
+            .insert(Dying(Timer::from_seconds(duration, TimerMode::Once)))
+            .remove::<(Collider, Velocity)>();
     }
 }
 
-fn despawn_dead_entities(mut commands: Commands, entities_q: Query<(Entity, &Health)>) {
-    for (entity, health) in entities_q.iter() {
-        if health.amount == 0 {
+fn despawn_dying(
+    mut commands: Commands,
+    mut dying_q: Query<(Entity, &mut Dying, Option<&mut Sprite>)>,
+    time: Res<Time>,
+) {
+    for (entity, mut dying, sprite) in dying_q.iter_mut() {
+        dying.0.tick(time.delta());
+
+        if dying.0.finished() {
             commands.entity(entity).despawn_recursive();
+        } else if let Some(mut sprite) = sprite {
+            sprite.color.set_alpha(dying.0.fraction_remaining());
         }
     }
 }
 
 fn tick_damage_cooldown(
     mut commands: Commands,
-    mut entities_q: Query<(Entity, &mut DamageCooldown)>,
+    mut entities_q: Query<(Entity, &mut DamageCooldown, Option<&mut Visibility>)>,
     time: Res<Time>,
 ) {
-    for (entity, mut timer) in entities_q.iter_mut() {
+    for (entity, mut timer, visibility) in entities_q.iter_mut() {
         timer.cooldown.tick(time.delta());
 
         if timer.cooldown.just_finished() {
             commands.entity(entity).remove::<DamageCooldown>();
         }
+
+        let Some(mut visibility) = visibility else {
+            continue;
+        };
+
+        let blinks = (timer.cooldown.elapsed_secs() / BLINK_INTERVAL) as u32;
+
+        *visibility = if blinks % 2 == 0 || timer.cooldown.finished() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Logs every hit that landed, for balancing.
+fn log_damage(mut events: EventReader<DamageDealt>) {
+    for hit in events.read() {
+        debug!(
+            "{:?} took {} {:?} damage from {:?}, {} absorbed",
+            hit.target, hit.amount, hit.kind, hit.source, hit.absorbed
+        );
+    }
+}
+
+fn recharge_shields(mut shields_q: Query<&mut Shield>, time: Res<Time>) {
+    for mut shield in shields_q.iter_mut() {
+        shield.recharge_delay.tick(time.delta());
+
+        if shield.recharge_delay.finished() {
+            shield.amount =
+                (shield.amount + shield.recharge_rate * time.delta_seconds()).min(shield.capacity);
+        }
+    }
+}
+
+fn apply_max_health(mut entities_q: Query<(&mut Health, &Stats), Changed<Stats>>) {
+    for (mut health, stats) in entities_q.iter_mut() {
+        health.set_max(stats.get(Stat::MaxHealth).round() as u32);
+    }
+}
+
+/// Turns the `Regen` stat into healing, one whole point at a time.
+fn regenerate(
+    mut events: EventWriter<Heal>,
+    mut entities_q: Query<(Entity, &mut Health, &Stats)>,
+    time: Res<Time>,
+) {
+    for (entity, mut health, stats) in entities_q.iter_mut() {
+        if health.is_dead() || health.amount >= health.max {
+            health.regenerated = 0.;
+            continue;
+        }
+
+        health.regenerated += stats.get(Stat::Regen) * time.delta_seconds();
+
+        let amount = health.regenerated.floor();
+
+        if amount >= 1. {
+            health.regenerated -= amount;
+            events.send(Heal {
+                entity,
+                amount: amount as u32,
+            });
+        }
+    }
+}
+
+fn apply_healing(mut events: EventReader<Heal>, mut entities_q: Query<&mut Health>) {
+    for heal in events.read() {
+        if let Ok(mut health) = entities_q.get_mut(heal.entity) {
+            health.heal(heal.amount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        collision::{
+            detect_collisions, handle_collisions, Broadphase, CollisionEnded, ContinuousCollision,
+            Layer,
+        },
+        stats::Modifier,
+    };
+
+    #[test]
+    fn resistances_apply_before_armor_which_leaves_at_least_one_damage() {
+        let mut world = World::new();
+        world.init_resource::<Events<DamageDealt>>();
+
+        let mut stats = Stats::default();
+        stats.push("plate", Modifier::Flat(Stat::Armor, 2.));
+        let knight = world
+            .spawn((
+                Defense {
+                    armor: 1,
+                    resistances: HashMap::from([
+                        (DamageType::Fire, 0.5),
+                        (DamageType::Ice, 1.),
+                        (DamageType::Holy, -0.5),
+                    ]),
+                },
+                stats,
+            ))
+            .id();
+        let peasant = world.spawn_empty().id();
+
+        let mitigated = world.run_system_once(move |pipeline: DamagePipeline| {
+            [
+                pipeline.mitigate(knight, 10, DamageType::Physical),
+                pipeline.mitigate(knight, 10, DamageType::Fire),
+                pipeline.mitigate(knight, 2, DamageType::Physical),
+                pipeline.mitigate(knight, 10, DamageType::Ice),
+                pipeline.mitigate(knight, 10, DamageType::Holy),
+                pipeline.mitigate(peasant, 10, DamageType::Fire),
+            ]
+        });
+
+        assert_eq!(mitigated, [7, 2, 1, 0, 12, 10]);
+    }
+
+    #[test]
+    fn shields_absorb_damage_before_health() {
+        let mut world = World::new();
+        world.init_resource::<Events<DamageDealt>>();
+
+        let target = world.spawn(Shield::new(5, 1., 1.)).id();
+
+        let health = world.run_system_once(move |mut pipeline: DamagePipeline| {
+            let mut health = Health::new(20);
+            pipeline.apply(target, target, DamageType::Fire, 3, true, &mut health);
+            pipeline.apply(target, target, DamageType::Fire, 4, true, &mut health);
+            pipeline.apply(target, target, DamageType::Fire, 4, true, &mut health);
+            health.amount()
+        });
+
+        assert_eq!(health, 14);
+
+        let dealt: Vec<_> = world
+            .resource_mut::<Events<DamageDealt>>()
+            .drain()
+            .map(|dealt| (dealt.absorbed, dealt.amount))
+            .collect();
+        assert_eq!(dealt, [(3, 0), (2, 2), (0, 4)]);
+    }
+
+    fn spawn_enemy(world: &mut World, position: Vec2) -> Entity {
+        world
+            .spawn((
+                Enemy { speed: 0. },
+                Health::new(10),
+                Collider::circle(8.).with_layers(Layer::ENEMY, Layer::PLAYER_PROJECTILE),
+                Transform::from_translation(position.extend(0.)),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn health_fraction_follows_hits_and_heals() {
+        let mut health = Health::new(20);
+        health.hit(5, Entity::PLACEHOLDER);
+
+        assert_eq!(health.amount(), 15);
+        assert_eq!(health.max(), 20);
+        assert_eq!(health.fraction(), 0.75);
+
+        health.heal(100);
+        assert_eq!(health.fraction(), 1.);
+
+        health.hit(100, Entity::PLACEHOLDER);
+        assert_eq!(health.fraction(), 0.);
+    }
+
+    #[test]
+    fn fast_projectile_only_hits_the_nearest_enemy() {
+        let mut world = World::new();
+        world.init_resource::<Broadphase>();
+        world.init_resource::<Events<CollisionStarted>>();
+        world.init_resource::<Events<CollisionOngoing>>();
+        world.init_resource::<Events<CollisionEnded>>();
+        world.init_resource::<Events<DamageDealt>>();
+
+        // Spawned farthest first, so their collision events come in the wrong order.
+        let far = spawn_enemy(&mut world, Vec2::new(80., 0.));
+        let near = spawn_enemy(&mut world, Vec2::new(40., 0.));
+        let dagger = world
+            .spawn((
+                Projectile::new(0, 1.),
+                CollisionDamage::new(4),
+                Collider::circle(2.).with_layers(Layer::PLAYER_PROJECTILE, Layer::ENEMY),
+                ContinuousCollision::default(),
+                Transform::default(),
+            ))
+            .id();
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems(
+            (
+                detect_collisions,
+                handle_collisions::<Enemy>,
+                take_damage::<Enemy>,
+            )
+                .chain(),
+        );
+        schedule.run(&mut world);
+
+        // Crosses both enemies within a single step.
+        world.get_mut::<Transform>(dagger).unwrap().translation.x = 120.;
+        schedule.run(&mut world);
+
+        assert_eq!(world.get::<Health>(near).unwrap().amount(), 6);
+        assert_eq!(world.get::<Health>(far).unwrap().amount(), 10);
     }
 }
@@ -3,7 +3,10 @@ use std::collections::{HashMap, HashSet};
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 
-use crate::collision::Collider;
+use crate::{
+    collision::{Collider, Layer},
+    state::{despawn_run_entities, GameState, RunEntity},
+};
 
 pub struct LevelsPlugin;
 
@@ -11,7 +14,10 @@ impl Plugin for LevelsPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(LevelSelection::index(0))
             .register_ldtk_int_cell::<WallBundle>(1)
-            .add_systems(Startup, load_levels)
+            .add_systems(
+                OnEnter(GameState::Loading),
+                load_levels.after(despawn_run_entities),
+            )
             .add_systems(Update, add_wall_colliders);
     }
 }
@@ -28,10 +34,13 @@ pub struct WallBundle {
 }
 
 fn load_levels(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn(LdtkWorldBundle {
-        ldtk_handle: asset_server.load("levels.ldtk"),
-        ..default()
-    });
+    commands.spawn((
+        RunEntity,
+        LdtkWorldBundle {
+            ldtk_handle: asset_server.load("levels.ldtk"),
+            ..default()
+        },
+    ));
 }
 
 fn add_wall_colliders(
@@ -194,6 +203,10 @@ fn add_wall_colliders(
                             Collider::with_size_and_offset(
                                 IVec2::new(width, height).as_vec2(),
                                 offset,
+                            )
+                            .with_layers(
+                                Layer::WALL,
+                                Layer::PLAYER | Layer::ENEMY | Layer::ENEMY_PROJECTILE,
                             ),
                             SpatialBundle {
                                 transform: Transform::from_xyz(
@@ -2,15 +2,29 @@ use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 
 mod asset_loader;
+mod broadphase;
 mod camera;
+mod chests;
 mod collision;
 mod combat;
-pub mod ghost;
+mod config;
+pub mod enemy;
+mod evolutions;
+mod experience;
+mod healing;
 mod health;
 mod levels;
 mod movement;
+mod narrowphase;
+mod navigation;
 pub mod player;
 pub mod schedule;
+mod state;
+mod stats;
+mod status;
+mod upgrades;
+mod waves;
+mod weapons;
 
 fn main() {
     App::new()
@@ -34,15 +48,26 @@ fn main() {
         )
         .add_plugins(LdtkPlugin)
         // my plugins
-        .add_plugins(schedule::SchedulePlugin)
+        .add_plugins(schedule::SchedulePlugin { tick_rate: 64. })
+        .add_plugins(state::GameStatePlugin)
         .add_plugins(asset_loader::AssetLoaderPlugin)
+        .add_plugins(config::ConfigPlugin)
         .add_plugins(camera::CameraPlugin)
+        .add_plugins(chests::ChestsPlugin)
         .add_plugins(levels::LevelsPlugin)
         .add_plugins(combat::CombatPlugin)
         .add_plugins(collision::CollisionPlugin)
-        .add_plugins(ghost::GhostPlugin)
+        .add_plugins(enemy::EnemyPlugin)
+        .add_plugins(evolutions::EvolutionsPlugin)
+        .add_plugins(experience::ExperiencePlugin)
+        .add_plugins(healing::HealingPlugin)
         .add_plugins(health::HealthPlugin)
         .add_plugins(movement::MovementPlugin)
+        .add_plugins(navigation::NavigationPlugin)
         .add_plugins(player::PlayerPlugin)
+        .add_plugins(status::StatusPlugin)
+        .add_plugins(upgrades::UpgradesPlugin)
+        .add_plugins(waves::WavesPlugin)
+        .add_plugins(weapons::WeaponsPlugin)
         .run();
 }
@@ -1,11 +1,12 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
 
 use crate::{
-    collision::{Collider, CollisionEvent},
-    ghost::Ghost,
+    collision::{Collider, CollisionOngoing, CollisionStarted, SpatialQuery},
+    enemy::Enemy,
     levels::WallTile,
     player::Player,
-    schedule::InGame,
+    schedule::{InGame, Presentation},
+    state::GameState,
 };
 
 pub struct MovementPlugin;
@@ -13,14 +14,29 @@ pub struct MovementPlugin;
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
-            Update,
+            FixedUpdate,
             (
                 update_position,
+                separate_crowd,
                 keep_inside_walls::<Player>,
-                keep_inside_walls::<Ghost>,
+                keep_inside_walls::<Enemy>,
             )
                 .chain()
                 .in_set(InGame::EntityUpdate),
+        )
+        .add_systems(
+            FixedFirst,
+            restore_simulated_translation.run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            FixedLast,
+            record_simulated_translation.run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            PostUpdate,
+            interpolate_translation
+                .run_if(in_state(GameState::Playing))
+                .in_set(Presentation::Interpolate),
         );
     }
 }
@@ -48,9 +64,28 @@ impl Velocity {
     }
 }
 
+/// Softly pushes the entity out of other colliders on its own layer that also
+/// have `Separation`, so crowds spread out instead of stacking.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Separation {
+    /// Fraction of the overlap resolved per second.
+    pub strength: f32,
+    /// Only the deepest overlapping neighbours count towards the push.
+    pub max_neighbours: usize,
+}
+
+/// Translation before and after the latest fixed tick. Between ticks the rendered
+/// `Transform` is blended between the two so motion stays smooth at any frame rate.
+#[derive(Component, Default, Debug)]
+pub struct TransformInterpolation {
+    previous: Option<Vec3>,
+    current: Option<Vec3>,
+}
+
 #[derive(Bundle, Default, Debug)]
 pub struct MovementBundle {
     pub velocity: Velocity,
+    pub interpolation: TransformInterpolation,
 }
 
 fn update_position(mut query: Query<(&Velocity, &mut Transform)>, time: Res<Time>) {
@@ -59,32 +94,122 @@ fn update_position(mut query: Query<(&Velocity, &mut Transform)>, time: Res<Time
     }
 }
 
+fn restore_simulated_translation(
+    mut entities_q: Query<(&mut TransformInterpolation, &mut Transform)>,
+) {
+    for (mut interpolation, mut transform) in entities_q.iter_mut() {
+        if let Some(current) = interpolation.current {
+            transform.translation = current;
+        }
+
+        interpolation.previous = Some(transform.translation);
+    }
+}
+
+fn record_simulated_translation(mut entities_q: Query<(&mut TransformInterpolation, &Transform)>) {
+    for (mut interpolation, transform) in entities_q.iter_mut() {
+        interpolation.current = Some(transform.translation);
+    }
+}
+
+fn interpolate_translation(
+    mut entities_q: Query<(&TransformInterpolation, &mut Transform)>,
+    time: Res<Time<Fixed>>,
+) {
+    for (interpolation, mut transform) in entities_q.iter_mut() {
+        if let (Some(previous), Some(current)) = (interpolation.previous, interpolation.current) {
+            transform.translation = previous.lerp(current, time.overstep_fraction());
+        }
+    }
+}
+
+fn separate_crowd(
+    spatial_query: SpatialQuery,
+    mut crowd_q: Query<(Entity, &mut Transform, &Collider, &Separation)>,
+    time: Res<Time>,
+) {
+    let crowd: HashMap<Entity, (Vec2, _)> = crowd_q
+        .iter()
+        .map(|(entity, transform, collider, _)| {
+            (
+                entity,
+                (
+                    transform.translation.truncate(),
+                    collider.shape_at(transform),
+                ),
+            )
+        })
+        .collect();
+
+    let mut pushes = Vec::new();
+
+    for (entity, _, collider, separation) in crowd_q.iter() {
+        let (position, shape) = crowd[&entity];
+
+        let mut overlaps: Vec<Vec2> = spatial_query
+            .aabb_query(shape.aabb(), collider.layer)
+            .into_iter()
+            .filter(|neighbour| *neighbour != entity)
+            .filter_map(|neighbour| {
+                let (neighbour_position, neighbour_shape) = crowd.get(&neighbour)?;
+                let push = shape.penetration(neighbour_shape)?;
+
+                // Entities stacked on the same spot get opposite pushes.
+                if position == *neighbour_position && entity < neighbour {
+                    Some(-push)
+                } else {
+                    Some(push)
+                }
+            })
+            .collect();
+
+        overlaps.sort_by(|a, b| b.length_squared().total_cmp(&a.length_squared()));
+
+        let push: Vec2 = overlaps.into_iter().take(separation.max_neighbours).sum();
+
+        // Both entities of an overlapping pair move, so each resolves half of it.
+        let rate = (separation.strength * time.delta_seconds()).min(1.);
+        pushes.push((entity, push * rate / 2.));
+    }
+
+    for (entity, push) in pushes {
+        if let Ok((_, mut transform, _, _)) = crowd_q.get_mut(entity) {
+            transform.translation += push.extend(0.);
+        }
+    }
+}
+
 fn keep_inside_walls<T: Component>(
-    mut events: EventReader<CollisionEvent>,
+    mut started_events: EventReader<CollisionStarted>,
+    mut ongoing_events: EventReader<CollisionOngoing>,
     mut entities_q: Query<(&mut Transform, &Collider), (With<T>, Without<WallTile>)>,
     walls_q: Query<(&Transform, &Collider), With<WallTile>>,
 ) {
-    for event in events.read() {
-        let Ok((mut entity_transform, entity_collider)) = entities_q.get_mut(event.entity) else {
+    let contacts = started_events
+        .read()
+        .map(|event| (event.entity, event.collided_with))
+        .chain(
+            ongoing_events
+                .read()
+                .map(|event| (event.entity, event.collided_with)),
+        );
+
+    for (entity, collided_with) in contacts {
+        let Ok((mut entity_transform, entity_collider)) = entities_q.get_mut(entity) else {
             continue;
         };
 
-        let Ok((wall_transform, wall_collider)) = walls_q.get(event.collided_with) else {
+        let Ok((wall_transform, wall_collider)) = walls_q.get(collided_with) else {
             continue;
         };
 
-        let entity_rect = entity_collider.to_rect_at(&entity_transform);
-        let wall_rect = wall_collider.to_rect_at(&wall_transform);
+        let entity_shape = entity_collider.shape_at(&entity_transform);
+        let wall_shape = wall_collider.shape_at(wall_transform);
 
-        let overlap = entity_rect.intersect(wall_rect);
-
-        let base_push = if overlap.width() < overlap.height() {
-            Vec2::new(overlap.width(), 0.)
-        } else {
-            Vec2::new(0., overlap.height())
+        let Some(push_away) = entity_shape.penetration(&wall_shape) else {
+            continue;
         };
 
-        let push_away = base_push * (entity_rect.center() - wall_rect.center()).signum();
         entity_transform.translation += push_away.extend(0.);
     }
 }
@@ -0,0 +1,515 @@
+use bevy::prelude::*;
+
+const EPSILON: f32 = 1e-5;
+const MIN_SWEEP_STEP: f32 = 0.5;
+const SWEEP_BISECTIONS: u32 = 8;
+
+/// A collider placed in the world: a convex core of one point (circle), two points
+/// (capsule) or four counter-clockwise corners (box), inflated by `radius`.
+#[derive(Debug, Clone, Copy)]
+pub struct WorldShape {
+    points: [Vec2; 4],
+    len: usize,
+    radius: f32,
+}
+
+impl WorldShape {
+    pub fn circle(center: Vec2, radius: f32) -> Self {
+        Self {
+            points: [center; 4],
+            len: 1,
+            radius,
+        }
+    }
+
+    pub fn capsule(start: Vec2, end: Vec2, radius: f32) -> Self {
+        Self {
+            points: [start, end, end, end],
+            len: 2,
+            radius,
+        }
+    }
+
+    pub fn oriented_box(center: Vec2, half_size: Vec2, axis_x: Vec2) -> Self {
+        let x = axis_x * half_size.x;
+        let y = axis_x.perp() * half_size.y;
+
+        Self {
+            points: [
+                center - x - y,
+                center + x - y,
+                center + x + y,
+                center - x + y,
+            ],
+            len: 4,
+            radius: 0.,
+        }
+    }
+
+    pub fn aabb(&self) -> Rect {
+        let (min, max) = self
+            .core()
+            .iter()
+            .fold((Vec2::MAX, Vec2::MIN), |(min, max), point| {
+                (min.min(*point), max.max(*point))
+            });
+
+        Rect::from_corners(min - self.radius, max + self.radius)
+    }
+
+    /// Returns the shortest translation that moves `self` out of `other`, or `None`
+    /// when the shapes don't overlap. Touching shapes don't count as overlapping.
+    pub fn penetration(&self, other: &WorldShape) -> Option<Vec2> {
+        let radii = self.radius + other.radius;
+        let (closest_self, closest_other) = self.closest_points(other);
+        let distance = closest_self.distance(closest_other);
+
+        if distance > EPSILON && !self.contains(closest_other) && !other.contains(closest_self) {
+            if distance >= radii {
+                return None;
+            }
+
+            let normal = (closest_self - closest_other) / distance;
+            return Some(normal * (radii - distance));
+        }
+
+        self.separating_axis_penetration(other)
+    }
+
+    pub fn translated(&self, offset: Vec2) -> Self {
+        let mut shape = *self;
+        shape.points.iter_mut().for_each(|point| *point += offset);
+        shape
+    }
+
+    /// Moves `self` by `motion` and returns the fraction of it travelled when the
+    /// shapes first overlap, or `None` when they never do.
+    pub fn time_of_impact(&self, motion: Vec2, other: &WorldShape) -> Option<f32> {
+        let overlaps_at = |time: f32| self.translated(motion * time).penetration(other).is_some();
+
+        // Steps no longer than half the thinnest shape can't skip over it.
+        let step = (self.thickness().min(other.thickness()) / 2.).max(MIN_SWEEP_STEP);
+        let steps = (motion.length() / step).ceil().max(1.) as u32;
+
+        if overlaps_at(0.) {
+            return Some(0.);
+        }
+
+        let mut last_clear = 0.;
+
+        for i in 1..=steps {
+            let time = i as f32 / steps as f32;
+
+            if overlaps_at(time) {
+                let mut first_hit = time;
+
+                for _ in 0..SWEEP_BISECTIONS {
+                    let middle = (last_clear + first_hit) / 2.;
+
+                    if overlaps_at(middle) {
+                        first_hit = middle;
+                    } else {
+                        last_clear = middle;
+                    }
+                }
+
+                return Some(first_hit);
+            }
+
+            last_clear = time;
+        }
+
+        None
+    }
+
+    /// Distance along the ray from `origin` in the unit `direction` to where it
+    /// first enters the shape, or `None` when it misses. Rays starting inside hit at 0.
+    pub fn raycast(&self, origin: Vec2, direction: Vec2) -> Option<f32> {
+        if WorldShape::circle(origin, 0.).penetration(self).is_some() {
+            return Some(0.);
+        }
+
+        let core = self.core();
+        let mut closest: Option<f32> = None;
+        let mut hit = |distance: Option<f32>| {
+            if let Some(distance) = distance {
+                if closest.map_or(true, |closest| distance < closest) {
+                    closest = Some(distance);
+                }
+            }
+        };
+
+        if self.radius > 0. {
+            for point in core.iter() {
+                hit(ray_circle(origin, direction, *point, self.radius));
+            }
+        }
+
+        // A capsule has one edge, a box closes its outline.
+        let edges = match core.len() {
+            1 => 0,
+            2 => 1,
+            len => len,
+        };
+
+        for i in 0..edges {
+            let (start, end) = (core[i], core[(i + 1) % core.len()]);
+            let offset = (end - start).perp().normalize_or_zero() * self.radius;
+
+            hit(ray_segment(origin, direction, start + offset, end + offset));
+
+            if self.radius > 0. {
+                hit(ray_segment(origin, direction, start - offset, end - offset));
+            }
+        }
+
+        closest
+    }
+
+    fn thickness(&self) -> f32 {
+        let core_thickness = match self.len {
+            4 => self.points[0]
+                .distance(self.points[1])
+                .min(self.points[1].distance(self.points[2])),
+            _ => 0.,
+        };
+
+        core_thickness + self.radius * 2.
+    }
+
+    fn core(&self) -> &[Vec2] {
+        &self.points[..self.len]
+    }
+
+    fn center(&self) -> Vec2 {
+        self.core().iter().sum::<Vec2>() / self.len as f32
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let core = self.core();
+        let edge_count = if self.len > 2 { self.len } else { 1 };
+
+        (0..edge_count).map(move |i| (core[i], core[(i + 1) % core.len()]))
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        self.len > 2
+            && self
+                .edges()
+                .all(|(start, end)| (end - start).perp_dot(point - start) >= 0.)
+    }
+
+    fn closest_points(&self, other: &WorldShape) -> (Vec2, Vec2) {
+        let mut closest = (self.points[0], other.points[0]);
+        let mut closest_distance = f32::MAX;
+
+        for (start_a, end_a) in self.edges() {
+            for (start_b, end_b) in other.edges() {
+                let points = closest_points_on_segments(start_a, end_a, start_b, end_b);
+                let distance = points.0.distance_squared(points.1);
+
+                if distance < closest_distance {
+                    closest = points;
+                    closest_distance = distance;
+                }
+            }
+        }
+
+        closest
+    }
+
+    fn separating_axis_penetration(&self, other: &WorldShape) -> Option<Vec2> {
+        let radii = self.radius + other.radius;
+        let mut axes: Vec<Vec2> = self
+            .edges()
+            .chain(other.edges())
+            .filter_map(|(start, end)| (end - start).perp().try_normalize())
+            .collect();
+
+        if axes.is_empty() {
+            axes.push(
+                (self.center() - other.center())
+                    .try_normalize()
+                    .unwrap_or(Vec2::Y),
+            );
+        }
+
+        let mut best: Option<(Vec2, f32)> = None;
+
+        for axis in axes {
+            let (min_self, max_self) = project(self.core(), axis);
+            let (min_other, max_other) = project(other.core(), axis);
+
+            let push_negative = max_self - min_other + radii;
+            let push_positive = max_other - min_self + radii;
+
+            if push_negative <= 0. || push_positive <= 0. {
+                return None;
+            }
+
+            let push = if push_negative < push_positive {
+                (-axis, push_negative)
+            } else {
+                (axis, push_positive)
+            };
+
+            if best.map_or(true, |(_, depth)| push.1 < depth) {
+                best = Some(push);
+            }
+        }
+
+        best.map(|(direction, depth)| direction * depth)
+    }
+}
+
+/// Distance along a ray with unit `direction` to a circle it starts outside of.
+fn ray_circle(origin: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let along = offset.dot(direction);
+    let outside = offset.length_squared() - radius * radius;
+
+    if outside > 0. && along > 0. {
+        return None;
+    }
+
+    let discriminant = along * along - outside;
+
+    if discriminant < 0. {
+        return None;
+    }
+
+    Some((-along - discriminant.sqrt()).max(0.))
+}
+
+/// Distance along a ray with unit `direction` to the segment from `start` to `end`.
+fn ray_segment(origin: Vec2, direction: Vec2, start: Vec2, end: Vec2) -> Option<f32> {
+    let edge = end - start;
+    let denominator = direction.perp_dot(edge);
+
+    if denominator.abs() < EPSILON {
+        return None;
+    }
+
+    let to_start = start - origin;
+    let distance = to_start.perp_dot(edge) / denominator;
+    let along_edge = to_start.perp_dot(direction) / denominator;
+
+    (distance >= 0. && (0. ..=1.).contains(&along_edge)).then_some(distance)
+}
+
+fn project(points: &[Vec2], axis: Vec2) -> (f32, f32) {
+    points
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), point| {
+            let projection = point.dot(axis);
+            (min.min(projection), max.max(projection))
+        })
+}
+
+/// Closest points between segments `p1..q1` and `p2..q2`, either of which may be
+/// degenerate, following Ericson's "Real-Time Collision Detection".
+fn closest_points_on_segments(p1: Vec2, q1: Vec2, p2: Vec2, q2: Vec2) -> (Vec2, Vec2) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.length_squared();
+    let e = d2.length_squared();
+    let f = d2.dot(r);
+
+    let (s, t) = if a <= EPSILON && e <= EPSILON {
+        (0., 0.)
+    } else if a <= EPSILON {
+        (0., (f / e).clamp(0., 1.))
+    } else {
+        let c = d1.dot(r);
+
+        if e <= EPSILON {
+            ((-c / a).clamp(0., 1.), 0.)
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+
+            let mut s = if denom > EPSILON {
+                ((b * f - c * e) / denom).clamp(0., 1.)
+            } else {
+                0.
+            };
+
+            let mut t = (b * s + f) / e;
+
+            if t < 0. {
+                t = 0.;
+                s = (-c / a).clamp(0., 1.);
+            } else if t > 1. {
+                t = 1.;
+                s = ((b - c) / a).clamp(0., 1.);
+            }
+
+            (s, t)
+        }
+    };
+
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(center: Vec2, half_size: f32) -> WorldShape {
+        WorldShape::oriented_box(center, Vec2::splat(half_size), Vec2::X)
+    }
+
+    fn assert_near(actual: Vec2, expected: Vec2) {
+        assert!(
+            actual.distance(expected) < 1e-3,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn circles_push_apart_along_their_centers() {
+        let a = WorldShape::circle(Vec2::ZERO, 5.);
+        let b = WorldShape::circle(Vec2::new(8., 0.), 5.);
+
+        assert_near(a.penetration(&b).unwrap(), Vec2::new(-2., 0.));
+        assert_near(b.penetration(&a).unwrap(), Vec2::new(2., 0.));
+    }
+
+    #[test]
+    fn touching_shapes_do_not_overlap() {
+        let circle = WorldShape::circle(Vec2::new(10., 0.), 5.);
+
+        assert!(WorldShape::circle(Vec2::ZERO, 5.)
+            .penetration(&circle)
+            .is_none());
+        assert!(square(Vec2::ZERO, 5.).penetration(&circle).is_none());
+    }
+
+    #[test]
+    fn circle_is_pushed_out_of_a_box_side() {
+        let circle = WorldShape::circle(Vec2::new(7., 0.), 3.);
+
+        assert_near(
+            circle.penetration(&square(Vec2::ZERO, 5.)).unwrap(),
+            Vec2::new(1., 0.),
+        );
+    }
+
+    #[test]
+    fn circle_inside_a_box_leaves_through_the_nearest_side() {
+        let circle = WorldShape::circle(Vec2::new(1., 0.), 1.);
+
+        assert_near(
+            circle.penetration(&square(Vec2::ZERO, 5.)).unwrap(),
+            Vec2::new(5., 0.),
+        );
+    }
+
+    #[test]
+    fn capsule_lying_on_a_box_is_pushed_up() {
+        let capsule = WorldShape::capsule(Vec2::new(-10., 8.), Vec2::new(10., 8.), 4.);
+
+        assert_near(
+            capsule.penetration(&square(Vec2::ZERO, 5.)).unwrap(),
+            Vec2::new(0., 1.),
+        );
+        assert!(capsule
+            .translated(Vec2::new(0., 2.))
+            .penetration(&square(Vec2::ZERO, 5.))
+            .is_none());
+    }
+
+    #[test]
+    fn capsules_crossing_each_other_overlap() {
+        let horizontal = WorldShape::capsule(Vec2::new(-10., 0.), Vec2::new(10., 0.), 1.);
+        let vertical = WorldShape::capsule(Vec2::new(0., -10.), Vec2::new(0., 10.), 1.);
+
+        assert!(horizontal.penetration(&vertical).is_some());
+    }
+
+    #[test]
+    fn boxes_separate_along_the_shallowest_axis() {
+        let a = square(Vec2::ZERO, 5.);
+        let b = square(Vec2::new(9., 2.), 5.);
+
+        assert_near(a.penetration(&b).unwrap(), Vec2::new(-1., 0.));
+    }
+
+    #[test]
+    fn rotated_box_overlaps_through_its_corner() {
+        let diamond = WorldShape::oriented_box(
+            Vec2::new(11., 0.),
+            Vec2::splat(5.),
+            Vec2::from_angle(std::f32::consts::FRAC_PI_4),
+        );
+
+        // The corner reaches 11 - 5 * sqrt(2), about 3.93, into the square.
+        assert!(square(Vec2::ZERO, 5.).penetration(&diamond).is_some());
+        assert!(square(Vec2::new(-2., 0.), 5.)
+            .penetration(&diamond)
+            .is_none());
+    }
+
+    #[test]
+    fn time_of_impact_finds_the_first_contact() {
+        let mover = WorldShape::circle(Vec2::new(-10., 0.), 1.);
+        let target = WorldShape::circle(Vec2::ZERO, 1.);
+
+        let time = mover.time_of_impact(Vec2::new(20., 0.), &target).unwrap();
+
+        assert!((time - 0.4).abs() < 0.01, "got {time}");
+    }
+
+    #[test]
+    fn time_of_impact_catches_thin_walls_and_misses() {
+        let bullet = WorldShape::circle(Vec2::new(-50., 0.), 0.5);
+        let wall = WorldShape::oriented_box(Vec2::ZERO, Vec2::new(0.5, 10.), Vec2::X);
+
+        assert!(bullet.time_of_impact(Vec2::new(100., 0.), &wall).is_some());
+        assert!(bullet.time_of_impact(Vec2::new(0., 100.), &wall).is_none());
+    }
+
+    #[test]
+    fn time_of_impact_is_zero_when_already_overlapping() {
+        let capsule = WorldShape::capsule(Vec2::new(-2., 0.), Vec2::new(2., 0.), 1.);
+
+        assert_eq!(
+            capsule.time_of_impact(Vec2::new(10., 0.), &square(Vec2::ZERO, 1.)),
+            Some(0.)
+        );
+    }
+
+    #[test]
+    fn rays_hit_circles_capsules_and_boxes() {
+        let circle = WorldShape::circle(Vec2::new(10., 0.), 2.);
+        let capsule = WorldShape::capsule(Vec2::new(10., -5.), Vec2::new(10., 5.), 1.);
+        let diamond = WorldShape::oriented_box(
+            Vec2::new(10., 0.),
+            Vec2::splat(2.),
+            Vec2::from_angle(std::f32::consts::FRAC_PI_4),
+        );
+
+        let distance = |shape: WorldShape, origin: Vec2| shape.raycast(origin, Vec2::X).unwrap();
+
+        assert!((distance(circle, Vec2::ZERO) - 8.).abs() < 1e-4);
+        assert!((distance(capsule, Vec2::new(0., 3.)) - 9.).abs() < 1e-4);
+        assert!((distance(capsule, Vec2::new(0., 5.5)) - (10. - 0.75_f32.sqrt())).abs() < 1e-4);
+        assert!((distance(diamond, Vec2::ZERO) - (10. - 8_f32.sqrt())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn rays_starting_inside_hit_at_once_and_rays_pointing_away_miss() {
+        let capsule = WorldShape::capsule(Vec2::new(-5., 0.), Vec2::new(5., 0.), 1.);
+
+        assert_eq!(capsule.raycast(Vec2::new(3., 0.5), Vec2::Y), Some(0.));
+        assert_eq!(
+            square(Vec2::ZERO, 1.).raycast(Vec2::ZERO, Vec2::X),
+            Some(0.)
+        );
+        assert!(capsule.raycast(Vec2::new(0., 5.), Vec2::Y).is_none());
+        assert!(square(Vec2::new(0., 5.), 1.)
+            .raycast(Vec2::ZERO, Vec2::X)
+            .is_none());
+    }
+}
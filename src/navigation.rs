@@ -0,0 +1,614 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    levels::WallTile, movement::Velocity, narrowphase::WorldShape, player::Player,
+    schedule::InGame, state::GameState,
+};
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavGrid>()
+            .init_resource::<FlowField>()
+            .add_systems(OnEnter(GameState::Loading), reset_navigation)
+            .add_systems(Update, build_nav_grid)
+            .add_systems(
+                FixedUpdate,
+                (update_flow_field, follow_paths).in_set(InGame::EntityUpdate),
+            );
+    }
+}
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+const WAYPOINT_ARRIVAL_DISTANCE: f32 = 2.;
+
+/// Walkable cells of the current level, built from the LDtk `walls` IntGrid layer.
+#[derive(Resource, Debug, Default)]
+pub struct NavGrid {
+    origin: Vec2,
+    cell_size: f32,
+    size: IVec2,
+    walls: Vec<bool>,
+}
+
+impl NavGrid {
+    pub fn cell_at(&self, position: Vec2) -> IVec2 {
+        ((position - self.origin) / self.cell_size)
+            .floor()
+            .as_ivec2()
+    }
+
+    pub fn cell_center(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + 0.5) * self.cell_size
+    }
+
+    /// The level's pixel bounds in world space.
+    pub fn bounds(&self) -> Rect {
+        Rect::from_corners(
+            self.origin,
+            self.origin + self.size.as_vec2() * self.cell_size,
+        )
+    }
+
+    pub fn walkable_cells(&self) -> impl Iterator<Item = IVec2> + '_ {
+        (0..self.size.y)
+            .flat_map(move |y| (0..self.size.x).map(move |x| IVec2::new(x, y)))
+            .filter(|cell| self.is_walkable(*cell))
+    }
+
+    pub fn is_walkable(&self, cell: IVec2) -> bool {
+        self.index(cell).is_some_and(|index| !self.walls[index])
+    }
+
+    /// A* from `from` to `to`, returning waypoints in world space without the
+    /// starting point. Waypoints an agent of `radius` can walk between in a straight
+    /// line are merged so it doesn't walk cell to cell.
+    pub fn find_path(&self, from: Vec2, to: Vec2, radius: f32) -> Option<Vec<Vec2>> {
+        let start = self.cell_at(from);
+        let goal = self.cell_at(to);
+
+        self.index(start)?;
+        self.index(goal).filter(|_| self.is_walkable(goal))?;
+
+        let heuristic = |cell: IVec2| {
+            let delta = (goal - cell).abs();
+            STRAIGHT_COST * delta.max_element() as u32
+                + (DIAGONAL_COST - STRAIGHT_COST) * delta.min_element() as u32
+        };
+
+        let mut costs = vec![None; self.walls.len()];
+        let mut came_from = vec![None; self.walls.len()];
+        let mut frontier = BinaryHeap::new();
+
+        costs[self.index(start)?] = Some(0);
+        frontier.push(Reverse((heuristic(start), 0, start.x, start.y)));
+
+        while let Some(Reverse((_, cost, x, y))) = frontier.pop() {
+            let cell = IVec2::new(x, y);
+
+            if cell == goal {
+                break;
+            }
+
+            // A cheaper way to this cell was found after this entry was pushed.
+            if costs[self.index(cell)?].is_some_and(|best| cost > best) {
+                continue;
+            }
+
+            for (neighbour, step_cost) in self.neighbours(cell) {
+                let index = self.index(neighbour)?;
+                let neighbour_cost = cost + step_cost;
+
+                if costs[index].map_or(true, |best| neighbour_cost < best) {
+                    costs[index] = Some(neighbour_cost);
+                    came_from[index] = Some(cell);
+                    frontier.push(Reverse((
+                        neighbour_cost + heuristic(neighbour),
+                        neighbour_cost,
+                        neighbour.x,
+                        neighbour.y,
+                    )));
+                }
+            }
+        }
+
+        let mut cells = vec![goal];
+
+        while let Some(previous) = came_from[self.index(*cells.last()?)?] {
+            cells.push(previous);
+        }
+
+        if *cells.last()? != start {
+            return None;
+        }
+
+        cells.reverse();
+
+        let mut waypoints = vec![];
+        let mut anchor = from;
+
+        for (i, cell) in cells.iter().enumerate().skip(1) {
+            let point = if *cell == goal {
+                to
+            } else {
+                self.cell_center(*cell)
+            };
+
+            let next = cells.get(i + 1).map(|next| {
+                if *next == goal {
+                    to
+                } else {
+                    self.cell_center(*next)
+                }
+            });
+
+            if next.is_some_and(|next| self.has_line_of_sight(anchor, next, radius)) {
+                continue;
+            }
+
+            waypoints.push(point);
+            anchor = point;
+        }
+
+        if waypoints.last() != Some(&to) {
+            waypoints.push(to);
+        }
+
+        Some(waypoints)
+    }
+
+    /// Whether something of `radius` can move in a straight line between two points
+    /// without overlapping a wall cell or leaving the level.
+    pub fn has_line_of_sight(&self, from: Vec2, to: Vec2, radius: f32) -> bool {
+        if self.size == IVec2::ZERO {
+            return false;
+        }
+
+        let sweep = WorldShape::capsule(from, to, radius);
+        let bounds = sweep.aabb();
+
+        if bounds.union(self.bounds()) != self.bounds() {
+            return false;
+        }
+
+        let min = self.cell_at(bounds.min).max(IVec2::ZERO);
+        let max = self.cell_at(bounds.max).min(self.size - 1);
+        let half_cell = Vec2::splat(self.cell_size / 2.);
+
+        (min.y..=max.y)
+            .flat_map(|y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+            .filter(|cell| !self.is_walkable(*cell))
+            .all(|cell| {
+                let wall = WorldShape::oriented_box(self.cell_center(cell), half_cell, Vec2::X);
+                sweep.penetration(&wall).is_none()
+            })
+    }
+
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        let in_bounds = cell.cmpge(IVec2::ZERO).all() && cell.cmplt(self.size).all();
+
+        in_bounds.then(|| (cell.y * self.size.x + cell.x) as usize)
+    }
+
+    /// Walkable neighbours of `cell` with their step cost. Diagonal steps that
+    /// would cut a wall corner are skipped.
+    fn neighbours(&self, cell: IVec2) -> impl Iterator<Item = (IVec2, u32)> + '_ {
+        [
+            IVec2::X,
+            IVec2::NEG_X,
+            IVec2::Y,
+            IVec2::NEG_Y,
+            IVec2::ONE,
+            IVec2::NEG_ONE,
+            IVec2::new(1, -1),
+            IVec2::new(-1, 1),
+        ]
+        .into_iter()
+        .filter(move |step| {
+            self.is_walkable(cell + *step)
+                && (step.x == 0
+                    || step.y == 0
+                    || (self.is_walkable(cell + IVec2::new(step.x, 0))
+                        && self.is_walkable(cell + IVec2::new(0, step.y))))
+        })
+        .map(move |step| {
+            let cost = if step.x == 0 || step.y == 0 {
+                STRAIGHT_COST
+            } else {
+                DIAGONAL_COST
+            };
+
+            (cell + step, cost)
+        })
+    }
+}
+
+/// Next cell on the way to the player from every walkable cell, shared by all enemies.
+#[derive(Resource, Debug, Default)]
+pub struct FlowField {
+    target: Option<IVec2>,
+    next_cells: Vec<Option<IVec2>>,
+}
+
+impl FlowField {
+    /// Returns the direction towards the center of the next cell, or `None` outside
+    /// the level, inside walls, in the target cell itself and in cells that can't
+    /// reach the target.
+    pub fn direction_at(&self, nav_grid: &NavGrid, position: Vec2) -> Option<Vec2> {
+        let index = nav_grid.index(nav_grid.cell_at(position))?;
+        let next_cell = self.next_cells.get(index).copied().flatten()?;
+
+        Some((nav_grid.cell_center(next_cell) - position).normalize_or_zero())
+    }
+}
+
+/// Drives `Velocity` along a path planned on the `NavGrid`.
+#[derive(Component, Debug)]
+pub struct PathFollower {
+    pub speed: f32,
+    /// Radius of the agent, kept clear of walls when smoothing the path.
+    radius: f32,
+    destination: Option<Vec2>,
+    waypoints: Vec<Vec2>,
+}
+
+impl PathFollower {
+    pub fn new(speed: f32, radius: f32) -> Self {
+        Self {
+            speed,
+            radius,
+            destination: None,
+            waypoints: vec![],
+        }
+    }
+
+    /// Plans a new path on the next update, dropping the current one.
+    pub fn go_to(&mut self, destination: Vec2) {
+        self.destination = Some(destination);
+        self.waypoints.clear();
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.destination.is_none() && self.waypoints.is_empty()
+    }
+}
+
+fn reset_navigation(mut nav_grid: ResMut<NavGrid>, mut flow_field: ResMut<FlowField>) {
+    *nav_grid = NavGrid::default();
+    *flow_field = FlowField::default();
+}
+
+fn build_nav_grid(
+    mut nav_grid: ResMut<NavGrid>,
+    added_walls_q: Query<(), Added<WallTile>>,
+    walls_q: Query<(&GridCoords, &Parent), With<WallTile>>,
+    parent_q: Query<&Parent, Without<WallTile>>,
+    level_q: Query<(&Transform, &LevelIid)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    if added_walls_q.is_empty() {
+        return;
+    }
+
+    let Some(level_entity) = walls_q
+        .iter()
+        .find_map(|(_, parent)| parent_q.get(parent.get()).ok())
+        .map(|grandparent| grandparent.get())
+    else {
+        return;
+    };
+
+    let Ok((level_transform, level_iid)) = level_q.get(level_entity) else {
+        return;
+    };
+
+    let ldtk_project = ldtk_project_assets
+        .get(ldtk_projects.single())
+        .expect("Project should be loaded if level has spawned");
+
+    let level = ldtk_project
+        .as_standalone()
+        .get_loaded_level_by_iid(&level_iid.to_string())
+        .expect("Spawned level should exist in LDtk project");
+
+    let LayerInstance {
+        c_wid: width,
+        c_hei: height,
+        grid_size,
+        ..
+    } = level.layer_instances()[0];
+
+    *nav_grid = NavGrid {
+        origin: level_transform.translation.truncate(),
+        cell_size: grid_size as f32,
+        size: IVec2::new(width, height),
+        walls: vec![false; (width * height) as usize],
+    };
+
+    for (&grid_coords, parent) in walls_q.iter() {
+        let in_level = parent_q
+            .get(parent.get())
+            .is_ok_and(|grandparent| grandparent.get() == level_entity);
+
+        if let Some(index) = nav_grid
+            .index(IVec2::from(grid_coords))
+            .filter(|_| in_level)
+        {
+            nav_grid.walls[index] = true;
+        }
+    }
+}
+
+fn update_flow_field(
+    mut flow_field: ResMut<FlowField>,
+    nav_grid: Res<NavGrid>,
+    player_q: Query<&Transform, With<Player>>,
+) {
+    let Ok(player_transform) = player_q.get_single() else {
+        return;
+    };
+
+    let target = nav_grid.cell_at(player_transform.translation.truncate());
+
+    if flow_field.target == Some(target) && !nav_grid.is_changed() {
+        return;
+    }
+
+    let costs = integrate_costs(&nav_grid, target);
+
+    let next_cells = (0..nav_grid.walls.len())
+        .map(|index| {
+            let cell = IVec2::new(
+                index as i32 % nav_grid.size.x,
+                index as i32 / nav_grid.size.x,
+            );
+
+            if cell == target || !nav_grid.is_walkable(cell) {
+                return None;
+            }
+
+            nav_grid
+                .neighbours(cell)
+                .filter_map(|(neighbour, _)| {
+                    let cost = costs[nav_grid.index(neighbour)?]?;
+                    Some((neighbour, cost))
+                })
+                .min_by_key(|(_, cost)| *cost)
+                .map(|(neighbour, _)| neighbour)
+        })
+        .collect();
+
+    *flow_field = FlowField {
+        target: Some(target),
+        next_cells,
+    };
+}
+
+/// Dijkstra over the grid from `target`, returning the cost to reach it from every cell.
+fn integrate_costs(nav_grid: &NavGrid, target: IVec2) -> Vec<Option<u32>> {
+    let mut costs = vec![None; nav_grid.walls.len()];
+    let mut frontier = BinaryHeap::new();
+
+    if let Some(index) = nav_grid.index(target) {
+        costs[index] = Some(0);
+        frontier.push(Reverse((0, target.x, target.y)));
+    }
+
+    while let Some(Reverse((cost, x, y))) = frontier.pop() {
+        let cell = IVec2::new(x, y);
+
+        if costs[nav_grid.index(cell).unwrap()].is_some_and(|best| cost > best) {
+            continue;
+        }
+
+        for (neighbour, step_cost) in nav_grid.neighbours(cell) {
+            let index = nav_grid.index(neighbour).unwrap();
+            let neighbour_cost = cost + step_cost;
+
+            if costs[index].map_or(true, |best| neighbour_cost < best) {
+                costs[index] = Some(neighbour_cost);
+                frontier.push(Reverse((neighbour_cost, neighbour.x, neighbour.y)));
+            }
+        }
+    }
+
+    costs
+}
+
+fn follow_paths(
+    nav_grid: Res<NavGrid>,
+    mut followers_q: Query<(&mut PathFollower, &mut Velocity, &Transform)>,
+) {
+    for (mut follower, mut velocity, transform) in followers_q.iter_mut() {
+        let position = transform.translation.truncate();
+
+        if let Some(destination) = follower.destination.take() {
+            let mut waypoints = nav_grid
+                .find_path(position, destination, follower.radius)
+                .unwrap_or_default();
+            waypoints.reverse();
+            follower.waypoints = waypoints;
+        }
+
+        while follower
+            .waypoints
+            .last()
+            .is_some_and(|waypoint| waypoint.distance(position) < WAYPOINT_ARRIVAL_DISTANCE)
+        {
+            follower.waypoints.pop();
+        }
+
+        let direction = follower
+            .waypoints
+            .last()
+            .map_or(Vec2::ZERO, |waypoint| *waypoint - position);
+
+        velocity.change_direction_speed(direction.extend(0.), follower.speed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    /// A grid of 10 pixel cells with its origin at zero. Rows are listed from
+    /// `y = 0` upwards and `#` marks a wall.
+    fn grid(rows: &[&str]) -> NavGrid {
+        let size = IVec2::new(rows[0].len() as i32, rows.len() as i32);
+
+        NavGrid {
+            origin: Vec2::ZERO,
+            cell_size: 10.,
+            size,
+            walls: rows
+                .iter()
+                .flat_map(|row| row.chars().map(|tile| tile == '#'))
+                .collect(),
+        }
+    }
+
+    fn flow_field(nav_grid: NavGrid, player_cell: IVec2) -> (NavGrid, FlowField) {
+        let mut world = World::new();
+        let player_position = nav_grid.cell_center(player_cell);
+        world.insert_resource(nav_grid);
+        world.init_resource::<FlowField>();
+        world.spawn((
+            Player,
+            Transform::from_translation(player_position.extend(0.)),
+        ));
+
+        world.run_system_once(update_flow_field);
+
+        let flow_field = world.remove_resource::<FlowField>().unwrap();
+        (world.remove_resource::<NavGrid>().unwrap(), flow_field)
+    }
+
+    #[test]
+    fn flow_field_leads_around_walls_to_the_player() {
+        let (nav_grid, flow_field) = flow_field(
+            grid(&[
+                "..#...", //
+                "..#.#.", //
+                "..#.#.", //
+                "....#.", //
+            ]),
+            IVec2::ZERO,
+        );
+
+        for start in nav_grid.walkable_cells() {
+            let mut cell = start;
+
+            for _ in 0..nav_grid.walls.len() {
+                let Some(next) = flow_field.next_cells[nav_grid.index(cell).unwrap()] else {
+                    break;
+                };
+                assert!(nav_grid.is_walkable(next));
+                assert!((next - cell).abs().max_element() == 1);
+                cell = next;
+            }
+
+            assert_eq!(cell, IVec2::ZERO, "stuck at {cell} coming from {start}");
+        }
+
+        // Straight up out of the dead end between the walls.
+        let direction = flow_field.direction_at(&nav_grid, Vec2::new(35., 15.));
+        assert_eq!(direction, Some(Vec2::Y));
+    }
+
+    #[test]
+    fn flow_field_has_no_direction_in_walls_or_walled_off_cells() {
+        let (nav_grid, flow_field) = flow_field(
+            grid(&[
+                "...#.", //
+                "...#.", //
+                "...##", //
+            ]),
+            IVec2::ZERO,
+        );
+
+        assert!(flow_field
+            .direction_at(&nav_grid, Vec2::new(35., 5.))
+            .is_none());
+        assert!(flow_field
+            .direction_at(&nav_grid, Vec2::new(45., 5.))
+            .is_none());
+        assert!(flow_field
+            .direction_at(&nav_grid, Vec2::new(5., 5.))
+            .is_none());
+        assert!(flow_field
+            .direction_at(&nav_grid, Vec2::new(25., 5.))
+            .is_some());
+    }
+
+    #[test]
+    fn paths_go_around_walls_and_skip_cells_in_line_of_sight() {
+        let nav_grid = grid(&[
+            ".....", //
+            "..#..", //
+            "..#..", //
+            ".....", //
+        ]);
+
+        let path = nav_grid
+            .find_path(Vec2::new(5., 15.), Vec2::new(45., 15.), 2.)
+            .unwrap();
+
+        // Smoothed to the cell under the wall, then straight to the goal.
+        assert_eq!(path, vec![Vec2::new(25., 5.), Vec2::new(45., 15.)]);
+
+        for segment in path.windows(2) {
+            assert!(nav_grid.has_line_of_sight(segment[0], segment[1], 2.));
+        }
+    }
+
+    #[test]
+    fn no_path_into_walls_or_walled_off_cells() {
+        let nav_grid = grid(&[
+            "..#..", //
+            "..#..", //
+        ]);
+
+        assert!(nav_grid
+            .find_path(Vec2::new(5., 5.), Vec2::new(25., 5.), 2.)
+            .is_none());
+        assert!(nav_grid
+            .find_path(Vec2::new(5., 5.), Vec2::new(45., 5.), 2.)
+            .is_none());
+        assert_eq!(
+            nav_grid.find_path(Vec2::new(5., 5.), Vec2::new(15., 15.), 2.),
+            Some(vec![Vec2::new(15., 15.)])
+        );
+    }
+
+    #[test]
+    fn line_of_sight_is_blocked_by_walls_by_radius_and_by_the_level_edge() {
+        let nav_grid = grid(&[
+            ".....", //
+            "..#..", //
+            ".....", //
+        ]);
+
+        assert!(!nav_grid.has_line_of_sight(Vec2::new(5., 15.), Vec2::new(45., 15.), 1.));
+        assert!(nav_grid.has_line_of_sight(Vec2::new(5., 5.), Vec2::new(45., 5.), 1.));
+        assert!(!nav_grid.has_line_of_sight(Vec2::new(5., 5.), Vec2::new(45., 5.), 6.));
+        assert!(!nav_grid.has_line_of_sight(Vec2::new(5., 5.), Vec2::new(-20., 5.), 1.));
+    }
+
+    #[test]
+    fn line_of_sight_is_blocked_before_the_grid_is_built() {
+        let nav_grid = NavGrid::default();
+
+        assert!(!nav_grid.has_line_of_sight(Vec2::ZERO, Vec2::new(100., 0.), 1.));
+        assert!(nav_grid
+            .find_path(Vec2::ZERO, Vec2::new(100., 0.), 1.)
+            .is_none());
+    }
+}
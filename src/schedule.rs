@@ -1,25 +1,38 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, transform::TransformSystem};
 
-pub struct SchedulePlugin;
+use crate::state::GameState;
+
+/// Runs the `InGame` sets in `FixedUpdate` at `tick_rate` ticks per second.
+pub struct SchedulePlugin {
+    pub tick_rate: f64,
+}
 
 impl Plugin for SchedulePlugin {
     fn build(&self, app: &mut App) {
-        app.configure_sets(
-            Update,
-            (
-                InGame::ProcessCombat,
-                InGame::UserInput,
-                InGame::EntityUpdate,
-                InGame::CollisionDetection,
+        app.insert_resource(Time::<Fixed>::from_hz(self.tick_rate))
+            .configure_sets(
+                FixedUpdate,
+                (
+                    InGame::ProcessCombat,
+                    InGame::UserInput,
+                    InGame::EntityUpdate,
+                    InGame::CollisionDetection,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .configure_sets(
+                PostUpdate,
+                (Presentation::Interpolate, Presentation::FollowCamera)
+                    .chain()
+                    .before(TransformSystem::TransformPropagate),
             )
-                .chain(),
-        )
-        .add_systems(
-            Update,
-            apply_deferred
-                .before(InGame::UserInput)
-                .after(InGame::ProcessCombat),
-        );
+            .add_systems(
+                FixedUpdate,
+                apply_deferred
+                    .before(InGame::UserInput)
+                    .after(InGame::ProcessCombat),
+            );
     }
 }
 
@@ -30,3 +43,10 @@ pub enum InGame {
     EntityUpdate,
     CollisionDetection,
 }
+
+/// Per-frame sets that turn the fixed-timestep simulation into what is rendered.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub enum Presentation {
+    Interpolate,
+    FollowCamera,
+}
@@ -0,0 +1,245 @@
+use bevy::prelude::*;
+
+use crate::{
+    asset_loader::LoadErrors,
+    config::GameplayConfig,
+    enemy::EnemyRegistry,
+    evolutions::EvolutionRecipes,
+    health::{DamageSystems, DeathKind, Dying, EntityDied},
+    player::Player,
+    schedule::InGame,
+    upgrades::UpgradePool,
+    waves::SpawnTable,
+    weapons::WeaponLibrary,
+};
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .enable_state_scoped_entities::<GameState>()
+            .add_systems(
+                OnEnter(GameState::MainMenu),
+                (despawn_run_entities, spawn_main_menu),
+            )
+            .add_systems(
+                OnEnter(GameState::Loading),
+                (despawn_run_entities, spawn_loading_screen),
+            )
+            .add_systems(OnEnter(GameState::Paused), spawn_pause_screen)
+            .add_systems(OnEnter(GameState::GameOver), spawn_game_over_screen)
+            .add_systems(
+                Update,
+                (
+                    start_run.run_if(in_state(GameState::MainMenu)),
+                    finish_loading.run_if(
+                        in_state(GameState::Loading)
+                            .and_then(resource_exists::<GameplayConfig>)
+                            .and_then(resource_exists::<EnemyRegistry>)
+                            .and_then(resource_exists::<SpawnTable>)
+                            .and_then(resource_exists::<UpgradePool>)
+                            .and_then(resource_exists::<EvolutionRecipes>),
+                    ),
+                    show_load_errors.run_if(in_state(GameState::Loading)),
+                    pause_run.run_if(in_state(GameState::Playing)),
+                    resume_run.run_if(in_state(GameState::Paused)),
+                    restart_run.run_if(in_state(GameState::GameOver)),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                detect_game_over
+                    .after(DamageSystems)
+                    .in_set(InGame::ProcessCombat),
+            );
+    }
+}
+
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    Loading,
+    Playing,
+    Paused,
+    LevelUp,
+    OpenChest,
+    GameOver,
+}
+
+/// Marks entities that belong to a single run. They are despawned when a new
+/// run starts or the game returns to the main menu.
+#[derive(Component, Debug, Default)]
+pub struct RunEntity;
+
+/// The smaller line of text under a screen's title.
+#[derive(Component, Debug)]
+struct ScreenHint;
+
+pub fn despawn_run_entities(mut commands: Commands, entities_q: Query<Entity, With<RunEntity>>) {
+    for entity in entities_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn start_run(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        next_state.set(GameState::Loading);
+    }
+}
+
+fn finish_loading(
+    player_q: Query<(), With<Player>>,
+    weapon_library: Res<WeaponLibrary>,
+    asset_server: Res<AssetServer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !player_q.is_empty() && asset_server.is_loaded_with_dependencies(&weapon_library.folder) {
+        next_state.set(GameState::Playing);
+    }
+}
+
+/// Lists the files that failed to load, since the run can't start without them.
+fn show_load_errors(errors: Res<LoadErrors>, mut hints_q: Query<(&mut Text, Ref<ScreenHint>)>) {
+    if errors.0.is_empty() {
+        return;
+    }
+
+    let message = errors
+        .0
+        .iter()
+        .map(|(path, error)| format!("{path}: {error}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    for (mut text, hint) in hints_q.iter_mut() {
+        if !errors.is_changed() && !hint.is_added() {
+            continue;
+        }
+
+        text.sections[0].value = format!("Failed to load\n{message}");
+        text.sections[0].style.color = Color::srgb(1., 0.4, 0.4);
+    }
+}
+
+fn pause_run(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Paused);
+    }
+}
+
+fn resume_run(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Playing);
+    } else if keyboard_input.just_pressed(KeyCode::KeyQ) {
+        next_state.set(GameState::MainMenu);
+    }
+}
+
+fn detect_game_over(
+    mut events: EventReader<EntityDied>,
+    player_q: Query<Has<Dying>, With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if let Some(death) = events.read().find(|death| death.kind == DeathKind::Player) {
+        info!("Player killed by {:?}", death.killer);
+        next_state.set(GameState::GameOver);
+        return;
+    }
+
+    // Another transition, like a level up, may have won the tick the player died.
+    if player_q.get_single().unwrap_or(true) {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+fn restart_run(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        next_state.set(GameState::Loading);
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::MainMenu);
+    }
+}
+
+fn spawn_main_menu(commands: Commands) {
+    spawn_screen(
+        commands,
+        GameState::MainMenu,
+        "Bevy Survivors",
+        "Press Enter to start",
+    );
+}
+
+fn spawn_loading_screen(commands: Commands) {
+    spawn_screen(commands, GameState::Loading, "Loading", "");
+}
+
+fn spawn_pause_screen(commands: Commands) {
+    spawn_screen(
+        commands,
+        GameState::Paused,
+        "Paused",
+        "Press Escape to resume or Q to quit",
+    );
+}
+
+fn spawn_game_over_screen(commands: Commands) {
+    spawn_screen(
+        commands,
+        GameState::GameOver,
+        "Game Over",
+        "Press Enter to restart or Escape for the menu",
+    );
+}
+
+fn spawn_screen(mut commands: Commands, state: GameState, title: &str, hint: &str) {
+    commands
+        .spawn((
+            StateScoped(state),
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.),
+                    ..default()
+                },
+                background_color: Color::srgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+        ))
+        .with_children(|screen| {
+            screen.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font_size: 48.,
+                    ..default()
+                },
+            ));
+            screen.spawn((
+                ScreenHint,
+                TextBundle::from_section(
+                    hint,
+                    TextStyle {
+                        font_size: 20.,
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
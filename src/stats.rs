@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Weapon cooldowns never drop below this fraction of their own.
+const MIN_COOLDOWN_MULTIPLIER: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Stat {
+    MaxHealth,
+    MoveSpeed,
+    /// Multiplier of weapon damage.
+    Might,
+    /// Multiplier of projectile size.
+    Area,
+    /// Fraction of weapon cooldowns removed.
+    Cooldown,
+    /// Extra projectiles per volley.
+    ProjectileCount,
+    /// Flat reduction of damage taken.
+    Armor,
+    /// Health regained per second.
+    Regen,
+    /// Radius in which pickups fly towards the player.
+    Magnet,
+    /// Multiplier of the odds of rare rewards.
+    Luck,
+}
+
+impl Stat {
+    /// Base value of the stat when none is given.
+    fn default_base(self) -> f32 {
+        match self {
+            Stat::Might | Stat::Area | Stat::Luck => 1.,
+            _ => 0.,
+        }
+    }
+}
+
+/// A change to a stat. Flat modifiers add to the base value, percent modifiers
+/// then scale the sum, so `Percent(Might, 0.1)` adds 10% might.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Modifier {
+    Flat(Stat, f32),
+    Percent(Stat, f32),
+}
+
+impl Modifier {
+    fn stat(self) -> Stat {
+        match self {
+            Modifier::Flat(stat, _) | Modifier::Percent(stat, _) => stat,
+        }
+    }
+}
+
+/// Base stats of an entity and the modifier stack items and upgrades push into.
+#[derive(Component, Debug, Default)]
+pub struct Stats {
+    base: HashMap<Stat, f32>,
+    /// Modifiers with the id of what granted them.
+    modifiers: Vec<(String, Modifier)>,
+}
+
+impl Stats {
+    pub fn set_base(&mut self, base: HashMap<Stat, f32>) {
+        self.base = base;
+    }
+
+    pub fn push(&mut self, source: &str, modifier: Modifier) {
+        self.modifiers.push((source.to_string(), modifier));
+    }
+
+    /// The value of `stat` with every modifier applied.
+    pub fn get(&self, stat: Stat) -> f32 {
+        let base = self.base.get(&stat).copied().unwrap_or(stat.default_base());
+
+        let (flat, percent) = self
+            .modifiers
+            .iter()
+            .filter(|(_, modifier)| modifier.stat() == stat)
+            .fold((0., 0.), |(flat, percent), (_, modifier)| match modifier {
+                Modifier::Flat(_, value) => (flat + value, percent),
+                Modifier::Percent(_, value) => (flat, percent + value),
+            });
+
+        (base + flat) * (1. + percent)
+    }
+
+    /// Factor weapon cooldowns are multiplied by.
+    pub fn cooldown_multiplier(&self) -> f32 {
+        (1. - self.get(Stat::Cooldown)).max(MIN_COOLDOWN_MULTIPLIER)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_modifiers_add_before_percent_modifiers_scale() {
+        let mut stats = Stats::default();
+        stats.set_base(HashMap::from([(Stat::MoveSpeed, 100.)]));
+
+        stats.push("boots", Modifier::Flat(Stat::MoveSpeed, 20.));
+        stats.push("wings", Modifier::Percent(Stat::MoveSpeed, 0.1));
+        stats.push("boots", Modifier::Flat(Stat::MoveSpeed, 30.));
+        stats.push("wings", Modifier::Percent(Stat::MoveSpeed, 0.1));
+        stats.push("spinach", Modifier::Percent(Stat::Might, 0.5));
+
+        assert!((stats.get(Stat::MoveSpeed) - 180.).abs() < 1e-4);
+        // Stats without a base start from their default.
+        assert!((stats.get(Stat::Might) - 1.5).abs() < 1e-4);
+        assert_eq!(stats.get(Stat::Armor), 0.);
+    }
+
+    #[test]
+    fn cooldown_multiplier_has_a_floor() {
+        let mut stats = Stats::default();
+        stats.push("tome", Modifier::Flat(Stat::Cooldown, 0.3));
+        assert!((stats.cooldown_multiplier() - 0.7).abs() < 1e-4);
+
+        stats.push("tome", Modifier::Flat(Stat::Cooldown, 0.6));
+        assert_eq!(stats.cooldown_multiplier(), MIN_COOLDOWN_MULTIPLIER);
+    }
+}
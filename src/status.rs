@@ -0,0 +1,295 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    health::{DamageSystems, DamageType, Dying, Hurt},
+    schedule::InGame,
+};
+
+pub struct StatusPlugin;
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            tick_statuses
+                .before(DamageSystems)
+                .in_set(InGame::ProcessCombat),
+        )
+        .add_systems(FixedUpdate, tint_disabled.in_set(InGame::EntityUpdate));
+    }
+}
+
+/// Seconds between two ticks of burn or poison damage.
+const TICK_INTERVAL: f32 = 0.5;
+/// Poison stacks beyond this replace the one closest to running out.
+const MAX_POISON_STACKS: usize = 5;
+const FREEZE_TINT: Color = Color::srgb(0.55, 0.8, 1.);
+const STUN_TINT: Color = Color::srgb(1., 0.95, 0.5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum StatusKind {
+    /// Deals fire damage every tick. Reapplying refreshes it.
+    Burn,
+    /// Deals physical damage every tick. Each application stacks.
+    Poison,
+    /// Removes a fraction of movement speed. The strongest slow applies.
+    Slow,
+    /// Stops movement and AI. Reapplying refreshes it.
+    Freeze,
+    /// Stops movement and AI. Reapplying refreshes it.
+    Stun,
+}
+
+/// A status effect a hit applies. `potency` is the damage per tick of burn and
+/// poison, and the fraction of speed a slow removes.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    /// Seconds the effect lasts.
+    pub duration: f32,
+    #[serde(default)]
+    pub potency: f32,
+}
+
+impl StatusEffect {
+    pub fn is_valid(&self) -> bool {
+        match self.kind {
+            StatusKind::Slow => self.duration > 0. && (0. ..=1.).contains(&self.potency),
+            _ => self.duration > 0. && self.potency >= 0.,
+        }
+    }
+}
+
+/// Status effects this entity applies to whatever it damages.
+#[derive(Component, Debug)]
+pub struct OnHitEffects(pub Vec<StatusEffect>);
+
+#[derive(Debug)]
+struct ActiveStatus {
+    effect: StatusEffect,
+    source: Entity,
+    remaining: Timer,
+    tick: Timer,
+}
+
+impl ActiveStatus {
+    fn new(effect: StatusEffect, source: Entity) -> Self {
+        Self {
+            effect,
+            source,
+            remaining: Timer::from_seconds(effect.duration, TimerMode::Once),
+            tick: Timer::from_seconds(TICK_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+/// The status effects currently affecting an entity.
+#[derive(Component, Debug, Default)]
+pub struct StatusEffects {
+    active: Vec<ActiveStatus>,
+    /// Sprite colour from before a freeze or stun tinted it.
+    base_color: Option<Color>,
+}
+
+impl StatusEffects {
+    pub fn add(&mut self, effect: StatusEffect, source: Entity) {
+        if effect.kind == StatusKind::Poison {
+            let stacks = self
+                .active
+                .iter()
+                .filter(|status| status.effect.kind == StatusKind::Poison)
+                .count();
+
+            if stacks >= MAX_POISON_STACKS {
+                let weakest = self
+                    .active
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, status)| status.effect.kind == StatusKind::Poison)
+                    .min_by_key(|(_, status)| status.remaining.remaining())
+                    .map(|(index, _)| index);
+
+                if let Some(index) = weakest {
+                    self.active.remove(index);
+                }
+            }
+
+            self.active.push(ActiveStatus::new(effect, source));
+            return;
+        }
+
+        let Some(status) = self
+            .active
+            .iter_mut()
+            .find(|status| status.effect.kind == effect.kind)
+        else {
+            self.active.push(ActiveStatus::new(effect, source));
+            return;
+        };
+
+        // Refreshing keeps the longer duration and the stronger potency.
+        if effect.duration > status.remaining.remaining_secs() {
+            status.remaining = Timer::from_seconds(effect.duration, TimerMode::Once);
+        }
+
+        if effect.potency > status.effect.potency {
+            status.effect.potency = effect.potency;
+            status.source = source;
+        }
+    }
+
+    /// Whether a freeze or stun keeps the entity from moving and acting.
+    pub fn is_disabled(&self) -> bool {
+        self.active
+            .iter()
+            .any(|status| matches!(status.effect.kind, StatusKind::Freeze | StatusKind::Stun))
+    }
+
+    /// Factor movement speed is multiplied by.
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.is_disabled() {
+            return 0.;
+        }
+
+        let slow = self
+            .active
+            .iter()
+            .filter(|status| status.effect.kind == StatusKind::Slow)
+            .map(|status| status.effect.potency)
+            .fold(0., f32::max);
+
+        1. - slow
+    }
+
+    fn tint(&self) -> Option<Color> {
+        self.active
+            .iter()
+            .find_map(|status| match status.effect.kind {
+                StatusKind::Freeze => Some(FREEZE_TINT),
+                StatusKind::Stun => Some(STUN_TINT),
+                _ => None,
+            })
+    }
+}
+
+/// Counts down status effects and sends the damage of those ticking.
+fn tick_statuses(
+    mut events: EventWriter<Hurt>,
+    mut entities_q: Query<(Entity, &mut StatusEffects), Without<Dying>>,
+    time: Res<Time>,
+) {
+    for (entity, mut statuses) in entities_q.iter_mut() {
+        for status in statuses.active.iter_mut() {
+            status.remaining.tick(time.delta());
+
+            let kind = match status.effect.kind {
+                StatusKind::Burn => DamageType::Fire,
+                StatusKind::Poison => DamageType::Physical,
+                _ => continue,
+            };
+
+            let ticks = status.tick.tick(time.delta()).times_finished_this_tick();
+            let amount = status.effect.potency.round() as u32 * ticks;
+
+            if amount > 0 {
+                events.send(Hurt {
+                    entity,
+                    source: status.source,
+                    amount,
+                    kind,
+                });
+            }
+        }
+
+        statuses
+            .active
+            .retain(|status| !status.remaining.finished());
+    }
+}
+
+/// Tints frozen and stunned entities, and gives them their colour back afterwards.
+fn tint_disabled(mut entities_q: Query<(&mut StatusEffects, &mut Sprite), Without<Dying>>) {
+    for (mut statuses, mut sprite) in entities_q.iter_mut() {
+        match (statuses.tint(), statuses.base_color) {
+            (Some(tint), None) => {
+                statuses.base_color = Some(sprite.color);
+                sprite.color = tint;
+            }
+            (Some(tint), Some(_)) if sprite.color != tint => sprite.color = tint,
+            (None, Some(base_color)) => {
+                sprite.color = base_color;
+                statuses.base_color = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(kind: StatusKind, duration: f32, potency: f32) -> StatusEffect {
+        StatusEffect {
+            kind,
+            duration,
+            potency,
+        }
+    }
+
+    fn durations(statuses: &StatusEffects, kind: StatusKind) -> Vec<f32> {
+        statuses
+            .active
+            .iter()
+            .filter(|status| status.effect.kind == kind)
+            .map(|status| status.remaining.remaining_secs())
+            .collect()
+    }
+
+    #[test]
+    fn poison_stacks_up_to_a_cap_replacing_the_one_closest_to_running_out() {
+        let mut statuses = StatusEffects::default();
+
+        for duration in [3., 1., 4., 2., 5.] {
+            statuses.add(
+                effect(StatusKind::Poison, duration, 1.),
+                Entity::PLACEHOLDER,
+            );
+        }
+        assert_eq!(
+            durations(&statuses, StatusKind::Poison),
+            [3., 1., 4., 2., 5.]
+        );
+
+        statuses.add(effect(StatusKind::Poison, 6., 1.), Entity::PLACEHOLDER);
+        assert_eq!(
+            durations(&statuses, StatusKind::Poison),
+            [3., 4., 2., 5., 6.]
+        );
+    }
+
+    #[test]
+    fn reapplying_keeps_the_longer_duration_and_the_stronger_potency() {
+        let mut statuses = StatusEffects::default();
+        let first = Entity::from_raw(1);
+        let second = Entity::from_raw(2);
+
+        statuses.add(effect(StatusKind::Burn, 4., 1.), first);
+        statuses.add(effect(StatusKind::Burn, 2., 3.), second);
+
+        let [burn] = &statuses.active[..] else {
+            panic!("burn should not stack");
+        };
+        assert_eq!(burn.remaining.remaining_secs(), 4.);
+        assert_eq!(burn.effect.potency, 3.);
+        assert_eq!(burn.source, second);
+
+        statuses.add(effect(StatusKind::Slow, 1., 0.2), first);
+        statuses.add(effect(StatusKind::Slow, 1., 0.5), first);
+        assert!((statuses.speed_multiplier() - 0.5).abs() < 1e-4);
+
+        statuses.add(effect(StatusKind::Stun, 1., 0.), first);
+        assert_eq!(statuses.speed_multiplier(), 0.);
+    }
+}
@@ -0,0 +1,498 @@
+use std::collections::HashSet;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+use crate::{
+    asset_loader::{RonAsset, RonResourcePlugin},
+    evolutions::EvolutionRecipes,
+    experience::Experience,
+    player::Player,
+    state::GameState,
+    stats::{Modifier, Stats},
+    weapons::{Weapon, WeaponCatalog, WeaponDefinition, Weapons},
+};
+
+pub struct UpgradesPlugin;
+
+impl Plugin for UpgradesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonResourcePlugin::<UpgradePool>::new(POOL_PATH))
+            .init_resource::<LevelUpChoices>()
+            .add_systems(
+                Update,
+                (
+                    choose_upgrade,
+                    roll_choices,
+                    show_choices.run_if(resource_changed::<LevelUpChoices>),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::LevelUp)),
+            );
+    }
+}
+
+const POOL_PATH: &str = "upgrades.pool.ron";
+const CHOICE_KEYS: [[KeyCode; 2]; 3] = [
+    [KeyCode::Digit1, KeyCode::Numpad1],
+    [KeyCode::Digit2, KeyCode::Numpad2],
+    [KeyCode::Digit3, KeyCode::Numpad3],
+];
+
+/// Everything a level up can offer, loaded from `assets/upgrades.pool.ron`.
+#[derive(Asset, Resource, TypePath, Debug, Clone, Deserialize)]
+pub struct UpgradePool {
+    /// Weapons the player can hold at once.
+    pub max_weapons: usize,
+    /// Weight of offering each weapon the player does not hold yet.
+    pub new_weapon_weight: f32,
+    /// Weight of offering the next level of each held weapon.
+    pub weapon_level_weight: f32,
+    pub passives: Vec<PassiveDefinition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PassiveDefinition {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub weight: f32,
+    pub max_level: u32,
+    /// Pushed into the player's `Stats` once per level.
+    pub modifiers: Vec<Modifier>,
+}
+
+impl RonAsset for UpgradePool {
+    const EXTENSIONS: &'static [&'static str] = &["pool.ron"];
+
+    fn validate(&self) -> Result<(), String> {
+        let mut errors = vec![];
+
+        if self.new_weapon_weight < 0. || self.weapon_level_weight < 0. {
+            errors.push("weapon weights must not be negative".to_string());
+        }
+
+        let mut ids = HashSet::new();
+
+        for passive in self.passives.iter() {
+            if !ids.insert(&passive.id) {
+                errors.push(format!("{}: duplicate passive id", passive.id));
+            }
+
+            if passive.weight <= 0. || passive.max_level == 0 {
+                errors.push(format!(
+                    "{}: weight and max_level must be positive",
+                    passive.id
+                ));
+            }
+
+            if passive.modifiers.is_empty() {
+                errors.push(format!("{}: needs modifiers", passive.id));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join(", "))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct HeldPassive {
+    id: String,
+    level: u32,
+}
+
+/// Passives held by an entity, with their levels.
+#[derive(Component, Debug, Default)]
+pub struct Passives(Vec<HeldPassive>);
+
+impl Passives {
+    pub fn level_of(&self, id: &str) -> u32 {
+        self.0
+            .iter()
+            .find(|passive| passive.id == id)
+            .map_or(0, |passive| passive.level)
+    }
+
+    fn add(&mut self, id: &str) {
+        match self.0.iter_mut().find(|passive| passive.id == id) {
+            Some(passive) => passive.level += 1,
+            None => self.0.push(HeldPassive {
+                id: id.to_string(),
+                level: 1,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Upgrade {
+    NewWeapon(Handle<WeaponDefinition>),
+    /// Index into the player's `Weapons`.
+    WeaponLevel(usize),
+    /// Index into `UpgradePool::passives`.
+    Passive(usize),
+    /// Replaces the weapon at an index of the player's `Weapons` with its evolution.
+    Evolution(usize, Handle<WeaponDefinition>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Choice {
+    upgrade: Upgrade,
+    pub title: String,
+    pub description: String,
+    /// Evolutions the upgraded weapon or passive is part of.
+    pub evolution: Option<String>,
+}
+
+/// Upgrades offered on the current level up screen. Empty while none is shown.
+#[derive(Resource, Debug, Default)]
+struct LevelUpChoices(Vec<Choice>);
+
+#[derive(Component, Debug)]
+struct ChoiceScreen;
+
+/// The player's weapons and passives, and everything they can be upgraded with.
+#[derive(SystemParam)]
+pub struct PlayerUpgrades<'w, 's> {
+    player_q: Query<
+        'w,
+        's,
+        (
+            &'static mut Weapons,
+            &'static mut Passives,
+            &'static mut Stats,
+        ),
+        With<Player>,
+    >,
+    catalog: WeaponCatalog<'w>,
+    pool: Res<'w, UpgradePool>,
+    recipes: Res<'w, EvolutionRecipes>,
+}
+
+impl PlayerUpgrades<'_, '_> {
+    /// Every upgrade the player could get right now, with its weight.
+    fn candidates(&self) -> Vec<(Choice, f32)> {
+        let Ok((weapons, passives, _)) = self.player_q.get_single() else {
+            return vec![];
+        };
+
+        let (catalog, pool, recipes) = (&self.catalog, &*self.pool, &*self.recipes);
+        let mut candidates = vec![];
+
+        if weapons.0.len() < pool.max_weapons {
+            for (handle, definition) in catalog.iter() {
+                if weapons.0.iter().any(|weapon| weapon.definition == handle)
+                    || recipes.is_evolved(&definition.id)
+                {
+                    continue;
+                }
+
+                let choice = Choice {
+                    upgrade: Upgrade::NewWeapon(handle),
+                    title: format!("New weapon: {}", definition.name),
+                    description: definition.description.clone(),
+                    evolution: recipes.hint(&definition.id, catalog, pool),
+                };
+
+                candidates.push((choice, pool.new_weapon_weight));
+            }
+        }
+
+        for (i, weapon) in weapons.0.iter().enumerate() {
+            let Some(definition) = catalog.get(&weapon.definition) else {
+                continue;
+            };
+
+            let Some(next_level) = definition.levels.get(weapon.level as usize - 1) else {
+                continue;
+            };
+
+            let choice = Choice {
+                upgrade: Upgrade::WeaponLevel(i),
+                title: format!("{} level {}", definition.name, weapon.level + 1),
+                description: next_level.description.clone(),
+                evolution: recipes.hint(&definition.id, catalog, pool),
+            };
+
+            candidates.push((choice, pool.weapon_level_weight));
+        }
+
+        for (i, passive) in pool.passives.iter().enumerate() {
+            let level = passives.level_of(&passive.id);
+
+            if level >= passive.max_level {
+                continue;
+            }
+
+            let choice = Choice {
+                upgrade: Upgrade::Passive(i),
+                title: format!("{} level {}", passive.name, level + 1),
+                description: passive.description.clone(),
+                evolution: recipes.hint(&passive.id, catalog, pool),
+            };
+
+            candidates.push((choice, passive.weight));
+        }
+
+        candidates.retain(|(_, weight)| *weight > 0.);
+        candidates
+    }
+
+    /// Up to `count` different upgrades drawn by weight. New weapons are only
+    /// offered when `new_weapons` is set.
+    fn roll(&self, count: usize, new_weapons: bool) -> Vec<Choice> {
+        let mut candidates = self.candidates();
+
+        if !new_weapons {
+            candidates.retain(|(choice, _)| !matches!(choice.upgrade, Upgrade::NewWeapon(_)));
+        }
+
+        candidates
+            .choose_multiple_weighted(&mut rand::thread_rng(), count, |(_, weight)| *weight)
+            .map_or(vec![], |rolled| {
+                rolled.map(|(choice, _)| choice.clone()).collect()
+            })
+    }
+
+    /// Evolution of the first held weapon whose recipe is complete.
+    fn ready_evolution(&self) -> Option<Choice> {
+        let (weapons, passives, _) = self.player_q.get_single().ok()?;
+
+        weapons.0.iter().enumerate().find_map(|(i, weapon)| {
+            let definition = self.catalog.get(&weapon.definition)?;
+
+            if weapon.level < definition.max_level() {
+                return None;
+            }
+
+            let recipe = self.recipes.ready(&definition.id, passives)?;
+            let (handle, evolved) = self.catalog.find(&recipe.evolved)?;
+
+            Some(Choice {
+                upgrade: Upgrade::Evolution(i, handle),
+                title: format!("{} evolves into {}", definition.name, evolved.name),
+                description: evolved.description.clone(),
+                evolution: None,
+            })
+        })
+    }
+
+    fn apply(&mut self, choice: &Choice) {
+        let Ok((mut weapons, mut passives, mut stats)) = self.player_q.get_single_mut() else {
+            return;
+        };
+
+        match choice.upgrade {
+            Upgrade::NewWeapon(ref handle) => {
+                if let Some(definition) = self.catalog.get(handle) {
+                    weapons
+                        .0
+                        .push(Weapon::new(handle.clone(), definition.cooldown));
+                }
+            }
+            Upgrade::WeaponLevel(i) => {
+                if let Some(weapon) = weapons.0.get_mut(i) {
+                    weapon.level += 1;
+                }
+            }
+            Upgrade::Passive(i) => {
+                if let Some(definition) = self.pool.passives.get(i) {
+                    passives.add(&definition.id);
+
+                    for modifier in definition.modifiers.iter() {
+                        stats.push(&definition.id, *modifier);
+                    }
+                }
+            }
+            Upgrade::Evolution(i, ref handle) => {
+                if let (Some(weapon), Some(definition)) =
+                    (weapons.0.get_mut(i), self.catalog.get(handle))
+                {
+                    *weapon = Weapon::new(handle.clone(), definition.cooldown);
+                }
+            }
+        }
+
+        info!("Applied upgrade {}", choice.title);
+    }
+
+    /// Applies up to `count` upgrades, evolving weapons whose recipes are complete
+    /// before anything else, and returns them.
+    pub fn open_chest(&mut self, count: u32) -> Vec<Choice> {
+        let mut rewards = vec![];
+
+        for _ in 0..count {
+            let Some(choice) = self.ready_evolution().or_else(|| self.roll(1, false).pop()) else {
+                break;
+            };
+
+            self.apply(&choice);
+            rewards.push(choice);
+        }
+
+        rewards
+    }
+}
+
+fn roll_choices(
+    mut choices: ResMut<LevelUpChoices>,
+    mut player_q: Query<&mut Experience, With<Player>>,
+    upgrades: PlayerUpgrades,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !choices.0.is_empty() {
+        return;
+    }
+
+    let Ok(mut experience) = player_q.get_single_mut() else {
+        return;
+    };
+
+    choices.0 = upgrades.roll(CHOICE_KEYS.len(), true);
+
+    // Everything is maxed out, so there is nothing to choose from.
+    if choices.0.is_empty() {
+        experience.pending_level_ups = 0;
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn choose_upgrade(
+    mut choices: ResMut<LevelUpChoices>,
+    mut player_q: Query<&mut Experience, With<Player>>,
+    mut upgrades: PlayerUpgrades,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(choice) = CHOICE_KEYS
+        .iter()
+        .position(|keys| keyboard_input.any_just_pressed(*keys))
+        .and_then(|i| choices.0.get(i))
+    else {
+        return;
+    };
+
+    let Ok(mut experience) = player_q.get_single_mut() else {
+        return;
+    };
+
+    upgrades.apply(choice);
+
+    choices.0.clear();
+    experience.pending_level_ups = experience.pending_level_ups.saturating_sub(1);
+
+    if experience.pending_level_ups == 0 {
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn show_choices(
+    mut commands: Commands,
+    choices: Res<LevelUpChoices>,
+    player_q: Query<&Experience, With<Player>>,
+    screens_q: Query<Entity, With<ChoiceScreen>>,
+    upgrades: PlayerUpgrades,
+) {
+    for entity in screens_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let Ok(experience) = player_q.get_single() else {
+        return;
+    };
+
+    if choices.0.is_empty() {
+        return;
+    }
+
+    // The level the choice is for, when several level ups are waiting.
+    let level = experience.level + 1 - experience.pending_level_ups;
+
+    commands
+        .spawn((
+            ChoiceScreen,
+            StateScoped(GameState::LevelUp),
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.),
+                    ..default()
+                },
+                background_color: Color::srgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+        ))
+        .with_children(|screen| {
+            screen.spawn(TextBundle::from_section(
+                format!("Level {level}"),
+                TextStyle {
+                    font_size: 48.,
+                    ..default()
+                },
+            ));
+
+            for (i, choice) in choices.0.iter().enumerate() {
+                let mut sections = vec![
+                    TextSection::new(
+                        format!("{}. {}\n", i + 1, choice.title),
+                        TextStyle {
+                            font_size: 24.,
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        choice.description.clone(),
+                        TextStyle {
+                            font_size: 16.,
+                            color: Color::srgb(0.8, 0.8, 0.8),
+                            ..default()
+                        },
+                    ),
+                ];
+
+                if let Some(evolution) = &choice.evolution {
+                    sections.push(TextSection::new(
+                        format!("\n{evolution}"),
+                        TextStyle {
+                            font_size: 16.,
+                            color: Color::srgb(1., 0.8, 0.3),
+                            ..default()
+                        },
+                    ));
+                }
+
+                screen.spawn(TextBundle::from_sections(sections));
+            }
+
+            // Weapons only evolve when a chest is opened, so point out those ready to.
+            if let Some(evolution) = upgrades.ready_evolution() {
+                screen.spawn(TextBundle::from_section(
+                    format!("Open a chest: {}", evolution.title),
+                    TextStyle {
+                        font_size: 20.,
+                        color: Color::srgb(1., 0.8, 0.3),
+                        ..default()
+                    },
+                ));
+            }
+
+            let keys: Vec<_> = (1..=choices.0.len()).map(|i| i.to_string()).collect();
+
+            screen.spawn(TextBundle::from_section(
+                format!("Press {}", keys.join(", ")),
+                TextStyle {
+                    font_size: 20.,
+                    ..default()
+                },
+            ));
+        });
+}
@@ -0,0 +1,368 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::{ecs::system::SystemParam, prelude::*, time::Stopwatch};
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+use crate::{
+    asset_loader::{RonAsset, RonResourcePlugin},
+    collision::{Layer, SpatialQuery},
+    enemy::{Enemy, EnemySpawner},
+    health::Dying,
+    navigation::NavGrid,
+    player::Player,
+    schedule::InGame,
+    state::GameState,
+};
+
+pub struct WavesPlugin;
+
+impl Plugin for WavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonResourcePlugin::<SpawnTable>::new(SPAWN_TABLE_PATH))
+            .init_resource::<WaveDirector>()
+            .add_systems(OnEnter(GameState::Loading), reset_wave_director)
+            .add_systems(FixedUpdate, direct_waves.in_set(InGame::EntityUpdate));
+    }
+}
+
+const SPAWN_TABLE_PATH: &str = "waves.table.ron";
+/// Distance between the camera view and the enemies spawned around it.
+const SPAWN_MARGIN: f32 = 16.;
+const SPAWN_ATTEMPTS: u32 = 16;
+
+/// What spawns when over the course of a run, loaded from `assets/waves.table.ron`.
+#[derive(Asset, Resource, TypePath, Debug, Clone, Deserialize)]
+pub struct SpawnTable {
+    /// Nothing spawns while this many enemies are alive. Formations are cut short
+    /// when they would go over it. Splitter children still spawn over the cap so
+    /// killing a splitter always splits it, but they count towards it once alive.
+    pub max_alive: u32,
+    pub waves: Vec<Wave>,
+    #[serde(default)]
+    pub events: Vec<SpawnEvent>,
+}
+
+/// Spawns one weighted random entry every `interval` seconds, from `start` seconds
+/// into the run until the next wave starts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Wave {
+    pub start: f32,
+    pub interval: f32,
+    /// Chance that a formation of this wave is led by an elite.
+    #[serde(default)]
+    pub elite_chance: f32,
+    pub entries: Vec<SpawnEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnEntry {
+    /// Id of an `EnemyRegistry` archetype.
+    pub archetype: String,
+    pub weight: f32,
+    pub formation: Formation,
+}
+
+/// A formation spawned once, `at` seconds into the run.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnEvent {
+    pub name: String,
+    pub at: f32,
+    pub archetype: String,
+    pub formation: Formation,
+    /// Whether the formation is led by an elite.
+    #[serde(default)]
+    pub elite: bool,
+}
+
+/// How a group of enemies is placed.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Formation {
+    /// Scattered within `spread` of a point outside the screen.
+    Cluster { count: u32, spread: f32 },
+    /// A line across the direction to the player, centered on a point outside the screen.
+    Line { count: u32, spacing: f32 },
+    /// Evenly spaced on a circle of `radius` around the player, widened to stay
+    /// outside the screen.
+    Ring { count: u32, radius: f32 },
+}
+
+impl RonAsset for SpawnTable {
+    const EXTENSIONS: &'static [&'static str] = &["table.ron"];
+
+    fn prepare(&mut self) {
+        self.waves.sort_by(|a, b| a.start.total_cmp(&b.start));
+        self.events.sort_by(|a, b| a.at.total_cmp(&b.at));
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut errors = vec![];
+
+        if !self.waves.iter().any(|wave| wave.start <= 0.) {
+            errors.push("a wave must start at 0".to_string());
+        }
+
+        for (i, wave) in self.waves.iter().enumerate() {
+            if wave.interval <= 0. {
+                errors.push(format!("wave {i}: interval must be positive"));
+            }
+
+            if wave.entries.is_empty() || wave.entries.iter().any(|entry| entry.weight <= 0.) {
+                errors.push(format!("wave {i}: needs entries with positive weights"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join(", "))
+        }
+    }
+}
+
+impl SpawnTable {
+    /// The wave that started last at `elapsed` seconds into the run.
+    fn wave_at(&self, elapsed: f32) -> Option<&Wave> {
+        self.waves.iter().rev().find(|wave| wave.start <= elapsed)
+    }
+}
+
+/// Progress of the current run through the `SpawnTable`.
+#[derive(Resource, Debug, Default)]
+struct WaveDirector {
+    elapsed: Stopwatch,
+    spawn_timer: Timer,
+    next_event: usize,
+}
+
+/// Picks where formations appear: just outside the camera view, inside the level
+/// and clear of walls.
+#[derive(SystemParam)]
+struct SpawnArea<'w, 's> {
+    camera_q: Query<'w, 's, (&'static Transform, &'static OrthographicProjection)>,
+    player_q: Query<'w, 's, &'static Transform, With<Player>>,
+    nav_grid: Res<'w, NavGrid>,
+    spatial_query: SpatialQuery<'w>,
+}
+
+impl SpawnArea<'_, '_> {
+    fn player_position(&self) -> Vec2 {
+        self.player_q
+            .get_single()
+            .map_or(Vec2::ZERO, |transform| transform.translation.truncate())
+    }
+
+    fn view(&self) -> Rect {
+        self.camera_q
+            .get_single()
+            .map_or(Rect::default(), |(transform, projection)| {
+                let center = transform.translation.truncate();
+                Rect::from_corners(center + projection.area.min, center + projection.area.max)
+            })
+    }
+
+    /// Whether an enemy of `radius` fits at `point` inside the level without
+    /// overlapping a wall.
+    fn is_clear(&self, point: Vec2, radius: f32) -> bool {
+        let bounds = self.nav_grid.bounds().inflate(-radius);
+
+        bounds.contains(point)
+            && self
+                .spatial_query
+                .circle_query(point, radius, Layer::WALL)
+                .is_empty()
+    }
+
+    /// A clear point just outside the camera view. When the view edge keeps landing
+    /// outside the level or in walls, falls back to a few random walkable cells out of view.
+    fn offscreen_point(&self, radius: f32, rng: &mut impl Rng) -> Option<Vec2> {
+        let view = self.view();
+        let edge = view.inflate(SPAWN_MARGIN + radius);
+
+        let on_edge = (0..SPAWN_ATTEMPTS).find_map(|_| {
+            let unit_vector = Vec2::from_angle(rng.gen_range((0.)..TAU));
+            let vector_on_square = unit_vector / unit_vector.x.abs().max(unit_vector.y.abs());
+            let point = edge.center() + vector_on_square * edge.half_size();
+
+            self.is_clear(point, radius).then_some(point)
+        });
+
+        on_edge.or_else(|| {
+            let candidates: Vec<_> = self
+                .nav_grid
+                .walkable_cells()
+                .map(|cell| self.nav_grid.cell_center(cell))
+                .filter(|point| !view.inflate(radius).contains(*point))
+                .collect();
+
+            candidates
+                .choose_multiple(rng, SPAWN_ATTEMPTS as usize)
+                .copied()
+                .find(|point| self.is_clear(*point, radius))
+        })
+    }
+
+    /// Clear positions for a formation of enemies of `radius`. Members that would
+    /// end up outside the level or in a wall are left out.
+    fn positions(&self, formation: Formation, radius: f32, rng: &mut impl Rng) -> Vec<Vec2> {
+        let player = self.player_position();
+
+        let positions: Vec<_> = match formation {
+            Formation::Cluster { count, spread } => {
+                let Some(center) = self.offscreen_point(radius, rng) else {
+                    return vec![];
+                };
+
+                (0..count)
+                    .map(|_| {
+                        let distance = spread * rng.gen::<f32>().sqrt();
+                        center + Vec2::from_angle(rng.gen_range((0.)..TAU)) * distance
+                    })
+                    .collect()
+            }
+            Formation::Line { count, spacing } => {
+                let Some(center) = self.offscreen_point(radius, rng) else {
+                    return vec![];
+                };
+
+                let across = (player - center).perp().normalize_or_zero();
+                let half_length = (count.saturating_sub(1)) as f32 * spacing / 2.;
+
+                (0..count)
+                    .map(|i| center + across * (i as f32 * spacing - half_length))
+                    .collect()
+            }
+            Formation::Ring {
+                count,
+                radius: ring_radius,
+            } => {
+                let view = self.view();
+                let farthest_corner = [
+                    view.min,
+                    view.max,
+                    Vec2::new(view.min.x, view.max.y),
+                    Vec2::new(view.max.x, view.min.y),
+                ]
+                .into_iter()
+                .map(|corner| corner.distance(player))
+                .fold(0., f32::max);
+                let ring_radius = ring_radius.max(farthest_corner + radius);
+
+                (0..count)
+                    .map(|i| player + Vec2::from_angle(TAU * i as f32 / count as f32) * ring_radius)
+                    .collect()
+            }
+        };
+
+        positions
+            .into_iter()
+            .filter(|position| self.is_clear(*position, radius))
+            .collect()
+    }
+}
+
+fn reset_wave_director(mut director: ResMut<WaveDirector>) {
+    *director = WaveDirector::default();
+}
+
+fn direct_waves(
+    mut director: ResMut<WaveDirector>,
+    mut spawner: EnemySpawner,
+    spawn_area: SpawnArea,
+    table: Res<SpawnTable>,
+    enemies_q: Query<(), (With<Enemy>, Without<Dying>)>,
+    time: Res<Time>,
+) {
+    director.elapsed.tick(time.delta());
+    let elapsed = director.elapsed.elapsed_secs();
+
+    let mut room = table
+        .max_alive
+        .saturating_sub(enemies_q.iter().count() as u32) as usize;
+
+    while let Some(event) = table
+        .events
+        .get(director.next_event)
+        .filter(|event| event.at <= elapsed)
+    {
+        info!("Spawn event {}", event.name);
+        director.next_event += 1;
+
+        room -= spawn_formation(
+            &mut spawner,
+            &spawn_area,
+            &event.archetype,
+            event.formation,
+            event.elite,
+            room,
+        );
+    }
+
+    let Some(wave) = table.wave_at(elapsed) else {
+        return;
+    };
+
+    let interval = Duration::from_secs_f32(wave.interval);
+
+    if director.spawn_timer.duration() != interval {
+        director.spawn_timer = Timer::new(interval, TimerMode::Repeating);
+    }
+
+    director.spawn_timer.tick(time.delta());
+
+    if !director.spawn_timer.just_finished() {
+        return;
+    }
+
+    let Ok(entry) = wave
+        .entries
+        .choose_weighted(&mut rand::thread_rng(), |entry| entry.weight)
+    else {
+        return;
+    };
+
+    let elite = rand::thread_rng().gen_bool(wave.elite_chance.clamp(0., 1.) as f64);
+
+    spawn_formation(
+        &mut spawner,
+        &spawn_area,
+        &entry.archetype,
+        entry.formation,
+        elite,
+        room,
+    );
+}
+
+/// Spawns up to `room` enemies of `archetype` in `formation`, the first one an elite
+/// when `elite` is set, returning how many spawned.
+fn spawn_formation(
+    spawner: &mut EnemySpawner,
+    spawn_area: &SpawnArea,
+    archetype: &str,
+    formation: Formation,
+    elite: bool,
+    room: usize,
+) -> usize {
+    let Some(radius) = spawner
+        .archetype(archetype)
+        .map(|archetype| archetype.radius)
+    else {
+        warn!("Unknown enemy archetype {archetype}");
+        return 0;
+    };
+
+    let positions = spawn_area.positions(formation, radius, &mut rand::thread_rng());
+
+    positions
+        .into_iter()
+        .take(room)
+        .enumerate()
+        .filter_map(|(i, position)| {
+            if elite && i == 0 {
+                spawner.spawn_elite(archetype, position)
+            } else {
+                spawner.spawn(archetype, position)
+            }
+        })
+        .count()
+}
@@ -0,0 +1,437 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::{asset::LoadedFolder, ecs::system::SystemParam, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    asset_loader::{RonAsset, RonAssetLoader, SpriteAssets},
+    collision::{Collider, CollisionDamage, ContinuousCollision, Layer},
+    health::{DamageDealt, DamageSystems, DamageType},
+    movement::{MovementBundle, Velocity},
+    player::Player,
+    schedule::InGame,
+    state::{GameState, RunEntity},
+    stats::{Stat, Stats},
+    status::{OnHitEffects, StatusEffect, StatusEffects},
+};
+
+pub struct WeaponsPlugin;
+
+impl Plugin for WeaponsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<WeaponDefinition>()
+            .init_asset_loader::<RonAssetLoader<WeaponDefinition>>()
+            .add_systems(Startup, load_weapon_library)
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Loading,
+                    entered: GameState::Playing,
+                },
+                equip_starting_weapons,
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    fire_weapons,
+                    // Projectiles must outlive the hits applying their effects.
+                    (apply_on_hit_effects, despawn_spent_projectiles)
+                        .chain()
+                        .after(DamageSystems),
+                )
+                    .in_set(InGame::ProcessCombat),
+            )
+            .add_systems(FixedUpdate, expire_projectiles.in_set(InGame::EntityUpdate));
+    }
+}
+
+const PROJECTILE_Z: f32 = 100.;
+
+/// A weapon as described by a `*.weapon.ron` file in `assets/weapons`.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct WeaponDefinition {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// Index of the sprite in the `SpriteAssets` tilemap.
+    pub sprite: usize,
+    /// Whether the player holds this weapon from the start of a run.
+    #[serde(default)]
+    pub starting: bool,
+    /// Seconds between volleys.
+    pub cooldown: f32,
+    /// Projectiles fired per volley.
+    pub projectile_count: u32,
+    pub spread: Spread,
+    pub speed: f32,
+    pub damage: u32,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    /// Enemies a projectile passes through before it is destroyed.
+    pub pierce: u32,
+    /// Seconds before a projectile that hit nothing disappears.
+    pub lifetime: f32,
+    /// Distance from the player at which projectiles appear.
+    pub spawn_distance: f32,
+    pub collider: ProjectileCollider,
+    /// Status effects projectiles apply to the enemies they damage.
+    #[serde(default)]
+    pub effects: Vec<StatusEffect>,
+    /// Bonuses gained at levels 2, 3 and so on.
+    #[serde(default)]
+    pub levels: Vec<WeaponLevel>,
+}
+
+/// Bonuses a weapon gains at one level, on top of those of the levels before.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WeaponLevel {
+    pub description: String,
+    pub damage: u32,
+    pub projectile_count: u32,
+    pub pierce: u32,
+    pub speed: f32,
+    /// Fraction of the base cooldown removed.
+    pub cooldown_reduction: f32,
+}
+
+/// Stats of a weapon at some level.
+#[derive(Debug, Clone, Copy)]
+pub struct WeaponStats {
+    pub cooldown: f32,
+    pub projectile_count: u32,
+    pub speed: f32,
+    pub damage: u32,
+    pub pierce: u32,
+}
+
+impl WeaponDefinition {
+    pub fn max_level(&self) -> u32 {
+        self.levels.len() as u32 + 1
+    }
+
+    pub fn stats_at(&self, level: u32) -> WeaponStats {
+        let base = WeaponStats {
+            cooldown: self.cooldown,
+            projectile_count: self.projectile_count,
+            speed: self.speed,
+            damage: self.damage,
+            pierce: self.pierce,
+        };
+
+        self.levels
+            .iter()
+            .take(level.saturating_sub(1) as usize)
+            .fold(base, |stats, bonus| WeaponStats {
+                cooldown: stats.cooldown - self.cooldown * bonus.cooldown_reduction,
+                projectile_count: stats.projectile_count + bonus.projectile_count,
+                speed: stats.speed + bonus.speed,
+                damage: stats.damage + bonus.damage,
+                pierce: stats.pierce + bonus.pierce,
+            })
+    }
+}
+
+impl RonAsset for WeaponDefinition {
+    const EXTENSIONS: &'static [&'static str] = &["weapon.ron"];
+
+    fn validate(&self) -> Result<(), String> {
+        if self.cooldown <= 0. {
+            return Err(format!("{}: cooldown must be positive", self.id));
+        }
+
+        if self.lifetime <= 0. {
+            return Err(format!("{}: lifetime must be positive", self.id));
+        }
+
+        if !self.effects.iter().all(StatusEffect::is_valid) {
+            return Err(format!(
+                "{}: effects need a positive duration and a valid potency",
+                self.id
+            ));
+        }
+
+        if self.stats_at(self.max_level()).cooldown <= 0. {
+            return Err(format!(
+                "{}: cooldown must stay positive at max level",
+                self.id
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// How the projectiles of a volley are aimed.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Spread {
+    /// Evenly spaced all around the player, starting straight up.
+    Radial,
+    /// Evenly spaced over `angle` degrees, centered on the direction the player moves in.
+    Fan { angle: f32 },
+}
+
+/// Capsule collider of a projectile, along the direction it flies in.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ProjectileCollider {
+    pub radius: f32,
+    pub half_length: f32,
+}
+
+/// Every weapon definition found in `assets/weapons`.
+#[derive(Resource, Debug)]
+pub struct WeaponLibrary {
+    pub folder: Handle<LoadedFolder>,
+}
+
+/// Loaded weapon definitions of the `WeaponLibrary`.
+#[derive(SystemParam)]
+pub struct WeaponCatalog<'w> {
+    library: Res<'w, WeaponLibrary>,
+    folders: Res<'w, Assets<LoadedFolder>>,
+    definitions: Res<'w, Assets<WeaponDefinition>>,
+}
+
+impl WeaponCatalog<'_> {
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<WeaponDefinition>, &WeaponDefinition)> {
+        self.folders
+            .get(&self.library.folder)
+            .into_iter()
+            .flat_map(|folder| folder.handles.iter())
+            .filter_map(|handle| handle.clone().try_typed::<WeaponDefinition>().ok())
+            .filter_map(|handle| {
+                let definition = self.definitions.get(&handle)?;
+                Some((handle, definition))
+            })
+    }
+
+    pub fn get(&self, handle: &Handle<WeaponDefinition>) -> Option<&WeaponDefinition> {
+        self.definitions.get(handle)
+    }
+
+    pub fn find(&self, id: &str) -> Option<(Handle<WeaponDefinition>, &WeaponDefinition)> {
+        self.iter().find(|(_, definition)| definition.id == id)
+    }
+
+    /// Whether every definition of the library has loaded.
+    pub fn is_loaded(&self) -> bool {
+        self.folders
+            .get(&self.library.folder)
+            .is_some_and(|folder| {
+                folder.handles.iter().all(|handle| {
+                    handle
+                        .clone()
+                        .try_typed::<WeaponDefinition>()
+                        .is_ok_and(|handle| self.definitions.contains(&handle))
+                })
+            })
+    }
+}
+
+#[derive(Debug)]
+pub struct Weapon {
+    pub definition: Handle<WeaponDefinition>,
+    pub level: u32,
+    cooldown: Timer,
+}
+
+impl Weapon {
+    pub fn new(definition: Handle<WeaponDefinition>, cooldown: f32) -> Self {
+        Self {
+            definition,
+            level: 1,
+            cooldown: Timer::from_seconds(cooldown, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Weapons held by an entity, each firing on its own cooldown.
+#[derive(Component, Debug, Default)]
+pub struct Weapons(pub Vec<Weapon>);
+
+#[derive(Component, Debug)]
+pub struct Projectile {
+    hits_left: u32,
+    lifetime: Timer,
+}
+
+impl Projectile {
+    /// A projectile that passes through `pierce` targets and disappears on the next
+    /// hit or after `lifetime` seconds.
+    pub fn new(pierce: u32, lifetime: f32) -> Self {
+        Self {
+            hits_left: pierce + 1,
+            lifetime: Timer::from_seconds(lifetime, TimerMode::Once),
+        }
+    }
+
+    /// Uses up one of the projectile's hits, or returns `false` when none are left.
+    pub fn hit(&mut self) -> bool {
+        if self.is_spent() {
+            return false;
+        }
+
+        self.hits_left -= 1;
+        true
+    }
+
+    pub fn is_spent(&self) -> bool {
+        self.hits_left == 0
+    }
+}
+
+fn load_weapon_library(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(WeaponLibrary {
+        folder: asset_server.load_folder("weapons"),
+    });
+}
+
+fn equip_starting_weapons(catalog: WeaponCatalog, mut player_q: Query<&mut Weapons, With<Player>>) {
+    let Ok(mut weapons) = player_q.get_single_mut() else {
+        return;
+    };
+
+    weapons.0 = catalog
+        .iter()
+        .filter(|(_, definition)| definition.starting)
+        .map(|(handle, definition)| {
+            info!("Equipped starting weapon {}", definition.id);
+            Weapon::new(handle, definition.cooldown)
+        })
+        .collect();
+}
+
+fn fire_weapons(
+    mut commands: Commands,
+    mut player_q: Query<(&mut Weapons, &Stats, &Transform, &Velocity), With<Player>>,
+    definitions: Res<Assets<WeaponDefinition>>,
+    sprites: Res<SpriteAssets>,
+    time: Res<Time>,
+) {
+    let Ok((mut weapons, stats, player_transform, player_velocity)) = player_q.get_single_mut()
+    else {
+        return;
+    };
+
+    let facing = player_velocity
+        .value
+        .truncate()
+        .try_normalize()
+        .unwrap_or(Vec2::Y);
+
+    let area = stats.get(Stat::Area);
+    let extra_projectiles = stats.get(Stat::ProjectileCount).round() as u32;
+
+    for weapon in weapons.0.iter_mut() {
+        let Some(definition) = definitions.get(&weapon.definition) else {
+            continue;
+        };
+
+        let level_stats = definition.stats_at(weapon.level);
+        let cooldown = Duration::from_secs_f32(level_stats.cooldown * stats.cooldown_multiplier());
+
+        if weapon.cooldown.duration() != cooldown {
+            weapon.cooldown.set_duration(cooldown);
+        }
+
+        weapon.cooldown.tick(time.delta());
+
+        if !weapon.cooldown.just_finished() {
+            continue;
+        }
+
+        let damage = (level_stats.damage as f32 * stats.get(Stat::Might)).round() as u32;
+        let count = level_stats.projectile_count + extra_projectiles;
+
+        for direction in volley_directions(definition.spread, count, facing) {
+            let mut transform = *player_transform;
+
+            transform.translation += (direction * definition.spawn_distance).extend(0.);
+            transform.translation.z = PROJECTILE_Z;
+            transform.rotation = Quat::from_rotation_z(Vec2::Y.angle_between(direction));
+            transform.scale = Vec3::splat(area);
+
+            commands.spawn((
+                Projectile::new(level_stats.pierce, definition.lifetime),
+                RunEntity,
+                sprites.tile(definition.sprite, transform),
+                Collider::capsule(
+                    definition.collider.radius * area,
+                    definition.collider.half_length * area,
+                )
+                .with_layers(Layer::PLAYER_PROJECTILE, Layer::ENEMY),
+                ContinuousCollision::default(),
+                CollisionDamage::new(damage).with_kind(definition.damage_type),
+                OnHitEffects(definition.effects.clone()),
+                MovementBundle {
+                    velocity: Velocity::from_direction_speed(
+                        direction.extend(0.),
+                        level_stats.speed,
+                    ),
+                    ..default()
+                },
+            ));
+        }
+    }
+}
+
+fn volley_directions(spread: Spread, count: u32, facing: Vec2) -> Vec<Vec2> {
+    (0..count)
+        .map(|i| match spread {
+            Spread::Radial => Vec2::Y.rotate(Vec2::from_angle(TAU * i as f32 / count as f32)),
+            Spread::Fan { angle } => {
+                let angle = angle.to_radians();
+                let offset = if count > 1 {
+                    angle * (i as f32 / (count - 1) as f32 - 0.5)
+                } else {
+                    0.
+                };
+
+                facing.rotate(Vec2::from_angle(offset))
+            }
+        })
+        .collect()
+}
+
+fn apply_on_hit_effects(
+    mut events: EventReader<DamageDealt>,
+    mut targets_q: Query<&mut StatusEffects>,
+    sources_q: Query<&OnHitEffects>,
+) {
+    // Status effects ticking must not apply the effects of their source again.
+    for hit in events.read().filter(|hit| hit.contact) {
+        let Ok(effects) = sources_q.get(hit.source) else {
+            continue;
+        };
+
+        let Ok(mut statuses) = targets_q.get_mut(hit.target) else {
+            continue;
+        };
+
+        for effect in effects.0.iter() {
+            statuses.add(*effect, hit.source);
+        }
+    }
+}
+
+/// Destroys projectiles once they have passed through as many targets as they can.
+fn despawn_spent_projectiles(mut commands: Commands, projectiles_q: Query<(Entity, &Projectile)>) {
+    for (entity, projectile) in projectiles_q.iter() {
+        if projectile.is_spent() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn expire_projectiles(
+    mut commands: Commands,
+    mut projectiles_q: Query<(Entity, &mut Projectile)>,
+    time: Res<Time>,
+) {
+    for (entity, mut projectile) in projectiles_q.iter_mut() {
+        projectile.lifetime.tick(time.delta());
+
+        if projectile.lifetime.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}